@@ -1,4 +1,5 @@
 use advent2019;
+use advent2019::ocr::{self, Bitmap};
 
 const WIDTH: usize = 25;
 const HEIGHT: usize = 6;
@@ -26,16 +27,17 @@ fn main() {
     }
     println!("{}", mul_result);
 
-    let mut image_data = vec!['_'; LAYER_SIZE];
+    // transparent pixels stay None until a layer below covers them
+    let mut image_data: Vec<Option<bool>> = vec![None; LAYER_SIZE];
     idx = 0;
     while idx < input.len() {
         let slice = &input[idx..idx + LAYER_SIZE];
         for (i, ch) in (0..LAYER_SIZE).zip(slice.chars()) {
-            if image_data[i] == '_' {
+            if image_data[i].is_none() {
                 image_data[i] = match ch {
-                    '0' => 'X',
-                    '1' => ' ',
-                    '2' => '_',
+                    '0' => Some(false),
+                    '1' => Some(true),
+                    '2' => None,
                     _ => panic!("unrecognized character"),
                 };
             }
@@ -43,11 +45,6 @@ fn main() {
         idx += LAYER_SIZE;
     }
 
-    for ptr in 0..LAYER_SIZE {
-        if ptr % WIDTH == 0 {
-            print!("\n");
-        }
-        print!("{}", image_data[ptr]);
-    }
-    print!("\n");
+    let image = Bitmap::from_fn(WIDTH, HEIGHT, |x, y| image_data[y * WIDTH + x] == Some(true));
+    println!("{}", ocr::recognize(&image, &ocr::SMALL).text_or_art());
 }
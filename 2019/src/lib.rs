@@ -1,4 +1,7 @@
 pub mod intcode;
+// shared with the other years
+#[path = "../../2022/src/ocr.rs"]
+pub mod ocr;
 
 use std::fs::File;
 use std::io::{BufReader, BufRead};
@@ -0,0 +1,483 @@
+use std::{
+    fmt::Display,
+    ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not},
+};
+
+use crate::{
+    ocr::Pixels,
+    tiles::{CoordType, Point, Tiles},
+};
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+/// Packed 2D grid of bits, one row after another, each row padded to a whole
+/// number of `u64` words.
+///
+/// Whole-grid operations work on a word at a time, so cellular automata can be
+/// stepped with a handful of shifts and boolean operations instead of visiting
+/// every cell. Bits past `width` in the last word of a row are always zero.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct BitGrid {
+    width: usize,
+    height: usize,
+    words_per_row: usize,
+    words: Vec<u64>,
+}
+
+impl BitGrid {
+    pub fn new(width: usize, height: usize) -> Self {
+        let words_per_row = width.div_ceil(WORD_BITS);
+        Self {
+            width,
+            height,
+            words_per_row,
+            words: vec![0; words_per_row * height],
+        }
+    }
+
+    pub fn from_fn(width: usize, height: usize, f: impl Fn(Point) -> bool) -> Self {
+        let mut grid = Self::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let p = Point::from((x, y));
+                if f(p) {
+                    grid.set(p, true);
+                }
+            }
+        }
+        grid
+    }
+
+    /// Set bits wherever the tile equals `on`.
+    pub fn from_tiles(tiles: &Tiles<u8>, on: u8) -> Self {
+        Self::from_fn(tiles.width(), tiles.height(), |p| tiles[p] == on)
+    }
+
+    pub const fn width(&self) -> usize {
+        self.width
+    }
+
+    pub const fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn contains(&self, p: Point) -> bool {
+        p.x >= 0 && p.y >= 0 && p.x < self.width as CoordType && p.y < self.height as CoordType
+    }
+
+    fn locate(&self, p: Point) -> (usize, u64) {
+        let (x, y) = (p.x as usize, p.y as usize);
+        (y * self.words_per_row + x / WORD_BITS, 1 << (x % WORD_BITS))
+    }
+
+    /// Bits outside the grid read as unset.
+    pub fn get(&self, p: Point) -> bool {
+        if !self.contains(p) {
+            return false;
+        }
+        let (idx, mask) = self.locate(p);
+        self.words[idx] & mask != 0
+    }
+
+    pub fn set(&mut self, p: Point, value: bool) {
+        assert!(self.contains(p));
+        let (idx, mask) = self.locate(p);
+        if value {
+            self.words[idx] |= mask;
+        } else {
+            self.words[idx] &= !mask;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.words.fill(0);
+    }
+
+    pub fn count_ones(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|&w| w == 0)
+    }
+
+    /// Iterate over the coordinates of all set bits, row by row.
+    pub fn iter(&self) -> impl Iterator<Item = Point> + '_ {
+        self.words.iter().enumerate().flat_map(move |(idx, &word)| {
+            let y = idx / self.words_per_row;
+            let base = (idx % self.words_per_row) * WORD_BITS;
+            let mut bits = word;
+            std::iter::from_fn(move || {
+                if bits == 0 {
+                    return None;
+                }
+                let x = base + bits.trailing_zeros() as usize;
+                bits &= bits - 1;
+                Some(Point::from((x, y)))
+            })
+        })
+    }
+
+    fn last_word_mask(&self) -> u64 {
+        match self.width % WORD_BITS {
+            0 => !0,
+            rem => (1 << rem) - 1,
+        }
+    }
+
+    fn rows_mut(&mut self) -> impl Iterator<Item = &mut [u64]> {
+        self.words.chunks_exact_mut(self.words_per_row)
+    }
+
+    fn shift_row_right(row: &mut [u64], last_mask: u64) {
+        let mut carry = 0;
+        for word in row.iter_mut() {
+            let next_carry = *word >> (WORD_BITS - 1);
+            *word = (*word << 1) | carry;
+            carry = next_carry;
+        }
+        if let Some(last) = row.last_mut() {
+            *last &= last_mask;
+        }
+    }
+
+    fn shift_row_left(row: &mut [u64]) {
+        let mut carry = 0;
+        for word in row.iter_mut().rev() {
+            let next_carry = *word & 1;
+            *word = (*word >> 1) | (carry << (WORD_BITS - 1));
+            carry = next_carry;
+        }
+    }
+
+    fn shift_horizontal(&mut self, dx: CoordType, wrap: bool) {
+        let last_mask = self.last_word_mask();
+        let top_bit = self.width - 1;
+        let top_mask = 1 << (top_bit % WORD_BITS);
+        let top_word = top_bit / WORD_BITS;
+        for row in self.rows_mut() {
+            match dx {
+                1 => {
+                    let wrapped = row[top_word] & top_mask != 0;
+                    Self::shift_row_right(row, last_mask);
+                    if wrap && wrapped {
+                        row[0] |= 1;
+                    }
+                }
+                -1 => {
+                    let wrapped = row[0] & 1 != 0;
+                    Self::shift_row_left(row);
+                    if wrap && wrapped {
+                        row[top_word] |= top_mask;
+                    }
+                }
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    fn shift_vertical(&mut self, dy: CoordType, wrap: bool) {
+        let row = self.words_per_row;
+        let len = self.words.len();
+        match (dy, wrap) {
+            (1, true) => self.words.rotate_right(row),
+            (-1, true) => self.words.rotate_left(row),
+            (1, false) => {
+                self.words.copy_within(..len - row, row);
+                self.words[..row].fill(0);
+            }
+            (-1, false) => {
+                self.words.copy_within(row.., 0);
+                self.words[len - row..].fill(0);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn shift_impl(&mut self, dir: Point, wrap: bool) {
+        assert!(dir.x.abs() <= 1 && dir.y.abs() <= 1);
+        if self.width == 0 || self.height == 0 {
+            return;
+        }
+        if dir.x != 0 {
+            self.shift_horizontal(dir.x, wrap);
+        }
+        if dir.y != 0 {
+            self.shift_vertical(dir.y, wrap);
+        }
+    }
+
+    /// Move every bit one step in `dir`, which may be any of the eight unit
+    /// directions. Bits moved past the edge are lost.
+    pub fn shift(&mut self, dir: Point) {
+        self.shift_impl(dir, false)
+    }
+
+    /// Like `shift`, but bits moved past the edge reappear on the other side.
+    pub fn rotate(&mut self, dir: Point) {
+        self.shift_impl(dir, true)
+    }
+
+    pub fn shifted(&self, dir: Point) -> Self {
+        let mut new = self.clone();
+        new.shift(dir);
+        new
+    }
+
+    /// Union of copies of the grid shifted in each of `dirs`.
+    pub fn shifted_union(&self, dirs: &[Point]) -> Self {
+        let mut result = Self::new(self.width, self.height);
+        for dir in dirs {
+            result |= &self.shifted(*dir);
+        }
+        result
+    }
+
+    /// For every cell, count how many of the cells at `cell + dir` for each
+    /// of `dirs` are set.
+    pub fn neighbor_counts(&self, dirs: &[Point]) -> NeighborCounts {
+        assert!(!dirs.is_empty());
+        let bits = usize::BITS - dirs.len().leading_zeros();
+        let mut planes = vec![Self::new(self.width, self.height); bits as usize];
+        for dir in dirs {
+            // neighbor at +dir is brought to the current cell by shifting in -dir
+            let mut carry = self.shifted(Point::new(-dir.x, -dir.y));
+            for plane in planes.iter_mut() {
+                for (p, c) in plane.words.iter_mut().zip(carry.words.iter_mut()) {
+                    let sum = *p ^ *c;
+                    *c &= *p;
+                    *p = sum;
+                }
+                if carry.is_empty() {
+                    break;
+                }
+            }
+        }
+        NeighborCounts { planes }
+    }
+
+    fn assert_same_size(&self, other: &Self) {
+        assert_eq!((self.width, self.height), (other.width, other.height));
+    }
+
+    /// Clear every bit that is set in `other`.
+    pub fn difference_with(&mut self, other: &Self) {
+        self.assert_same_size(other);
+        for (a, b) in self.words.iter_mut().zip(other.words.iter()) {
+            *a &= !*b;
+        }
+    }
+}
+
+/// Per-cell neighbor counts from `BitGrid::neighbor_counts`, stored as bit
+/// planes of a binary counter.
+pub struct NeighborCounts {
+    planes: Vec<BitGrid>,
+}
+
+impl NeighborCounts {
+    pub fn count_at(&self, p: Point) -> usize {
+        self.planes
+            .iter()
+            .enumerate()
+            .map(|(i, plane)| (plane.get(p) as usize) << i)
+            .sum()
+    }
+
+    /// Cells with exactly `n` neighbors.
+    pub fn exactly(&self, n: usize) -> BitGrid {
+        let mut result = !&BitGrid::new(self.planes[0].width, self.planes[0].height);
+        if n >> self.planes.len() != 0 {
+            result.clear();
+        }
+        for (i, plane) in self.planes.iter().enumerate() {
+            if n & (1 << i) != 0 {
+                result &= plane;
+            } else {
+                result.difference_with(plane);
+            }
+        }
+        result
+    }
+
+    /// Cells with at least `n` neighbors.
+    pub fn at_least(&self, n: usize) -> BitGrid {
+        let max = (1 << self.planes.len()) - 1;
+        let mut result = BitGrid::new(self.planes[0].width, self.planes[0].height);
+        for k in n..=max {
+            result |= &self.exactly(k);
+        }
+        result
+    }
+}
+
+impl BitOrAssign<&BitGrid> for BitGrid {
+    fn bitor_assign(&mut self, rhs: &BitGrid) {
+        self.assert_same_size(rhs);
+        for (a, b) in self.words.iter_mut().zip(rhs.words.iter()) {
+            *a |= *b;
+        }
+    }
+}
+
+impl BitAndAssign<&BitGrid> for BitGrid {
+    fn bitand_assign(&mut self, rhs: &BitGrid) {
+        self.assert_same_size(rhs);
+        for (a, b) in self.words.iter_mut().zip(rhs.words.iter()) {
+            *a &= *b;
+        }
+    }
+}
+
+impl BitXorAssign<&BitGrid> for BitGrid {
+    fn bitxor_assign(&mut self, rhs: &BitGrid) {
+        self.assert_same_size(rhs);
+        for (a, b) in self.words.iter_mut().zip(rhs.words.iter()) {
+            *a ^= *b;
+        }
+    }
+}
+
+impl BitOr for &BitGrid {
+    type Output = BitGrid;
+
+    fn bitor(self, rhs: Self) -> BitGrid {
+        let mut result = self.clone();
+        result |= rhs;
+        result
+    }
+}
+
+impl BitAnd for &BitGrid {
+    type Output = BitGrid;
+
+    fn bitand(self, rhs: Self) -> BitGrid {
+        let mut result = self.clone();
+        result &= rhs;
+        result
+    }
+}
+
+impl BitXor for &BitGrid {
+    type Output = BitGrid;
+
+    fn bitxor(self, rhs: Self) -> BitGrid {
+        let mut result = self.clone();
+        result ^= rhs;
+        result
+    }
+}
+
+impl Not for &BitGrid {
+    type Output = BitGrid;
+
+    fn not(self) -> BitGrid {
+        let mut result = self.clone();
+        let last_mask = result.last_word_mask();
+        for row in result.rows_mut() {
+            row.iter_mut().for_each(|w| *w = !*w);
+            if let Some(last) = row.last_mut() {
+                *last &= last_mask;
+            }
+        }
+        result
+    }
+}
+
+impl Display for BitGrid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let ch = if self.get(Point::from((x, y))) {
+                    '#'
+                } else {
+                    '.'
+                };
+                write!(f, "{}", ch)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl Pixels for BitGrid {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> bool {
+        self.get(Point::from((x, y)))
+    }
+}
+
+#[allow(unused)]
+mod test {
+    use super::*;
+
+    fn grid(rows: &[&str]) -> BitGrid {
+        BitGrid::from_fn(rows[0].len(), rows.len(), |p| {
+            rows[p.y as usize].as_bytes()[p.x as usize] == b'#'
+        })
+    }
+
+    #[test]
+    fn test_shift_and_rotate() {
+        let mut g = grid(&["#..#", "....", ".#.."]);
+        g.shift(Point::new(1, 0));
+        assert_eq!(g, grid(&[".#..", "....", "..#."]));
+        g.rotate(Point::new(0, 1));
+        assert_eq!(g, grid(&["..#.", ".#..", "...."]));
+        g.rotate(Point::new(-1, -1));
+        assert_eq!(g, grid(&["#...", "....", ".#.."]));
+        g.rotate(Point::new(-1, 0));
+        assert_eq!(g, grid(&["...#", "....", "#..."]));
+        g.shift(Point::new(1, 1));
+        assert!(g.is_empty());
+    }
+
+    #[test]
+    fn test_wide_rows() {
+        let width = 130;
+        let mut g = BitGrid::new(width, 2);
+        g.set(Point::new(63, 0), true);
+        g.set(Point::new(129, 1), true);
+        g.rotate(Point::new(1, 0));
+        assert_eq!(
+            g.iter().collect::<Vec<_>>(),
+            [Point::new(64, 0), Point::new(0, 1)]
+        );
+        g.rotate(Point::new(-1, 0));
+        g.rotate(Point::new(-1, 0));
+        assert_eq!(
+            g.iter().collect::<Vec<_>>(),
+            [Point::new(62, 0), Point::new(128, 1)]
+        );
+        assert_eq!((!&g).count_ones(), width * 2 - 2);
+    }
+
+    #[test]
+    fn test_neighbor_counts() {
+        let g = grid(&["###", "#.#", "..#"]);
+        let dirs = [
+            Point::new(-1, -1),
+            Point::new(0, -1),
+            Point::new(1, -1),
+            Point::new(-1, 0),
+            Point::new(1, 0),
+            Point::new(-1, 1),
+            Point::new(0, 1),
+            Point::new(1, 1),
+        ];
+        let counts = g.neighbor_counts(&dirs);
+        assert_eq!(counts.count_at(Point::new(1, 1)), 6);
+        assert_eq!(counts.count_at(Point::new(0, 0)), 2);
+        assert_eq!(counts.count_at(Point::new(2, 2)), 1);
+        assert_eq!(counts.exactly(6), grid(&["...", ".#.", "..."]));
+        assert_eq!(counts.at_least(3), grid(&[".#.", ".##", ".#."]));
+    }
+}
@@ -1,8 +1,9 @@
-use std::ops::{BitAndAssign, BitOrAssign, Shl};
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, Shl, Sub};
 
-use num_traits::{PrimInt, AsPrimitive};
+use num_traits::{AsPrimitive, PrimInt};
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+/// Set of small integers backed by a single primitive integer.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default)]
 pub struct BitSet<T: PrimInt + BitOrAssign>(T);
 
 impl<T> BitSet<T>
@@ -14,12 +15,12 @@ where
     }
 
     pub fn new_full_up_to<U: AsPrimitive<T>>(max: U) -> Self {
-        let bound = T::one() << max.as_() + T::one();
+        // shifting in two steps so that the top bit does not overflow
+        let bound = (!T::zero() << max.as_()) << T::one();
         Self(!bound)
     }
 
-    pub fn add<U: AsPrimitive<T>>(&mut self, c: U) 
-    {
+    pub fn add<U: AsPrimitive<T>>(&mut self, c: U) {
         self.0 |= T::one() << c.as_();
     }
 
@@ -27,8 +28,7 @@ where
         let mut new = *self;
         new.add(c);
         new
-    } 
-    
+    }
 
     pub fn contains<U: AsPrimitive<T>>(&self, c: U) -> bool {
         self.0 & (T::one() << c.as_()) != T::zero()
@@ -47,4 +47,274 @@ where
     pub fn len(&self) -> usize {
         self.0.count_ones() as usize
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_zero()
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        Self(self.0 & other.0)
+    }
+
+    pub fn difference(&self, other: &Self) -> Self {
+        Self(self.0 & !other.0)
+    }
+
+    pub fn is_subset(&self, other: &Self) -> bool {
+        self.difference(other).is_empty()
+    }
+
+    /// Iterate over the members in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = usize> {
+        let mut bits = self.0;
+        std::iter::from_fn(move || {
+            if bits.is_zero() {
+                return None;
+            }
+            let idx = bits.trailing_zeros() as usize;
+            bits &= bits - T::one();
+            Some(idx)
+        })
+    }
+
+    /// Iterate over all subsets of this set, starting with the set itself
+    /// and ending with the empty set.
+    pub fn subsets(&self) -> impl Iterator<Item = Self> {
+        let mask = self.0;
+        std::iter::successors(Some(*self), move |sub| {
+            (!sub.is_empty()).then(|| Self((sub.0 - T::one()) & mask))
+        })
+    }
+}
+
+/// Set of integers below `64 * WORDS`, for when one primitive integer is not
+/// enough. Offers the same interface as `BitSet`.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct WideBitSet<const WORDS: usize>([u64; WORDS]);
+
+impl<const WORDS: usize> Default for WideBitSet<WORDS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const WORDS: usize> WideBitSet<WORDS> {
+    pub const CAPACITY: usize = WORDS * u64::BITS as usize;
+
+    pub const fn new() -> Self {
+        Self([0; WORDS])
+    }
+
+    pub fn new_full_up_to<U: AsPrimitive<usize>>(max: U) -> Self {
+        let max = max.as_();
+        assert!(max < Self::CAPACITY);
+        let mut new = Self::new();
+        let (word, bit) = Self::split(max);
+        new.0[..word].fill(!0);
+        new.0[word] = !((!0 << bit) << 1);
+        new
+    }
+
+    fn split(c: usize) -> (usize, u32) {
+        (c / u64::BITS as usize, (c % u64::BITS as usize) as u32)
+    }
+
+    pub fn add<U: AsPrimitive<usize>>(&mut self, c: U) {
+        let (word, bit) = Self::split(c.as_());
+        self.0[word] |= 1 << bit;
+    }
+
+    pub fn with_added<U: AsPrimitive<usize>>(&self, c: U) -> Self {
+        let mut new = *self;
+        new.add(c);
+        new
+    }
+
+    pub fn contains<U: AsPrimitive<usize>>(&self, c: U) -> bool {
+        let (word, bit) = Self::split(c.as_());
+        self.0[word] & (1 << bit) != 0
+    }
+
+    pub fn remove<U: AsPrimitive<usize>>(&mut self, c: U) {
+        let (word, bit) = Self::split(c.as_());
+        self.0[word] &= !(1 << bit);
+    }
+
+    pub fn with_removed<U: AsPrimitive<usize>>(&self, c: U) -> Self {
+        let mut new = *self;
+        new.remove(c);
+        new
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|&w| w == 0)
+    }
+
+    fn zip_with(&self, other: &Self, f: impl Fn(u64, u64) -> u64) -> Self {
+        Self(std::array::from_fn(|i| f(self.0[i], other.0[i])))
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        self.zip_with(other, |a, b| a | b)
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        self.zip_with(other, |a, b| a & b)
+    }
+
+    pub fn difference(&self, other: &Self) -> Self {
+        self.zip_with(other, |a, b| a & !b)
+    }
+
+    pub fn is_subset(&self, other: &Self) -> bool {
+        self.difference(other).is_empty()
+    }
+
+    /// Iterate over the members in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.0.iter().enumerate().flat_map(|(i, &word)| {
+            let base = i * u64::BITS as usize;
+            BitSet(word).iter().map(move |idx| base + idx)
+        })
+    }
+
+    /// Multi-word `self - 1`, wrapping around on the empty set.
+    fn decremented(&self) -> Self {
+        let mut new = *self;
+        for word in new.0.iter_mut() {
+            let (res, borrow) = word.overflowing_sub(1);
+            *word = res;
+            if !borrow {
+                break;
+            }
+        }
+        new
+    }
+
+    /// Iterate over all subsets of this set, starting with the set itself
+    /// and ending with the empty set.
+    pub fn subsets(&self) -> impl Iterator<Item = Self> {
+        let mask = *self;
+        std::iter::successors(Some(mask), move |sub| {
+            (!sub.is_empty()).then(|| sub.decremented().intersection(&mask))
+        })
+    }
+}
+
+impl<const WORDS: usize> FromIterator<usize> for WideBitSet<WORDS> {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        let mut new = Self::new();
+        iter.into_iter().for_each(|c| new.add(c));
+        new
+    }
+}
+
+impl<T> BitOr for BitSet<T>
+where
+    T: PrimInt + BitOrAssign + BitAndAssign + Shl<Output = T> + 'static,
+{
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        self.union(&rhs)
+    }
+}
+
+impl<T> BitAnd for BitSet<T>
+where
+    T: PrimInt + BitOrAssign + BitAndAssign + Shl<Output = T> + 'static,
+{
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        self.intersection(&rhs)
+    }
+}
+
+impl<T> Sub for BitSet<T>
+where
+    T: PrimInt + BitOrAssign + BitAndAssign + Shl<Output = T> + 'static,
+{
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        self.difference(&rhs)
+    }
+}
+
+impl<const WORDS: usize> BitOr for WideBitSet<WORDS> {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        self.union(&rhs)
+    }
+}
+
+impl<const WORDS: usize> BitAnd for WideBitSet<WORDS> {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        self.intersection(&rhs)
+    }
+}
+
+impl<const WORDS: usize> Sub for WideBitSet<WORDS> {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        self.difference(&rhs)
+    }
+}
+
+#[allow(unused)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_full_up_to() {
+        assert_eq!(BitSet::<u64>::new_full_up_to(63).len(), 64);
+        assert_eq!(
+            BitSet::<u64>::new_full_up_to(4).iter().collect::<Vec<_>>(),
+            [0, 1, 2, 3, 4]
+        );
+        assert_eq!(WideBitSet::<2>::new_full_up_to(127).len(), 128);
+        assert_eq!(WideBitSet::<2>::new_full_up_to(64).len(), 65);
+        assert_eq!(WideBitSet::<2>::new_full_up_to(63).len(), 64);
+    }
+
+    #[test]
+    fn test_set_algebra() {
+        let a: WideBitSet<3> = [1, 5, 64, 130].into_iter().collect();
+        let b: WideBitSet<3> = [5, 64, 100].into_iter().collect();
+        assert_eq!((a | b).iter().collect::<Vec<_>>(), [1, 5, 64, 100, 130]);
+        assert_eq!((a & b).iter().collect::<Vec<_>>(), [5, 64]);
+        assert_eq!((a - b).iter().collect::<Vec<_>>(), [1, 130]);
+        assert!((a & b).is_subset(&a));
+        assert!(!a.is_subset(&b));
+    }
+
+    #[test]
+    fn test_subsets() {
+        let small = BitSet::<u32>::new()
+            .with_added(0)
+            .with_added(3)
+            .with_added(7);
+        assert_eq!(small.subsets().count(), 8);
+        assert!(small.subsets().all(|s| s.is_subset(&small)));
+
+        let wide: WideBitSet<2> = [3, 63, 64, 90].into_iter().collect();
+        let subsets = wide.subsets().collect::<Vec<_>>();
+        assert_eq!(subsets.len(), 16);
+        assert_eq!(subsets[0], wide);
+        assert!(subsets[15].is_empty());
+        assert!(subsets.iter().all(|s| s.is_subset(&wide)));
+    }
 }
@@ -0,0 +1,288 @@
+use std::{
+    collections::HashSet,
+    hash::Hash,
+    ops::AddAssign,
+    sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+};
+
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+
+/// Node of a maximizing branch-and-bound search.
+///
+/// `Problem` holds whatever is shared by all states, such as a graph or a
+/// blueprint, so that states themselves stay small.
+pub trait SearchState: Clone + Hash + Eq + Send {
+    type Problem: Sync;
+
+    /// Score of this state if the search stopped here.
+    fn score(&self, problem: &Self::Problem) -> u32;
+
+    /// Upper bound for the score of this state and everything reachable from
+    /// it. Must never underestimate, or the search may miss the optimum.
+    fn bound(&self, problem: &Self::Problem) -> u32;
+
+    /// Append the states reachable in one step to `out`, most promising first.
+    fn successors(&self, problem: &Self::Problem, out: &mut Vec<Self>);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SearchStats {
+    /// States whose successors were generated.
+    pub expanded: u64,
+    /// States discarded because their bound could not beat the best score.
+    pub pruned: u64,
+    /// States discarded because they were seen before.
+    pub transpositions: u64,
+}
+
+impl AddAssign for SearchStats {
+    fn add_assign(&mut self, rhs: Self) {
+        self.expanded += rhs.expanded;
+        self.pruned += rhs.pruned;
+        self.transpositions += rhs.transpositions;
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchResult<S> {
+    pub best_score: u32,
+    /// State that reached `best_score`, or `None` if nothing beat the root.
+    pub best_state: Option<S>,
+    pub stats: SearchStats,
+    /// False if the node budget ran out, in which case the result is only
+    /// the best found so far.
+    pub complete: bool,
+}
+
+impl<S> SearchResult<S> {
+    fn empty() -> Self {
+        Self {
+            best_score: 0,
+            best_state: None,
+            stats: SearchStats::default(),
+            complete: true,
+        }
+    }
+
+    fn merge(mut self, other: Self) -> Self {
+        if other.best_score > self.best_score {
+            self.best_score = other.best_score;
+            self.best_state = other.best_state;
+        }
+        self.stats += other.stats;
+        self.complete &= other.complete;
+        self
+    }
+}
+
+/// Depth-first branch-and-bound search maximizing `SearchState::score`.
+pub struct BranchAndBound<'p, S: SearchState> {
+    problem: &'p S::Problem,
+    transpositions: bool,
+    node_budget: Option<u64>,
+    parallel_depth: usize,
+}
+
+/// State shared by all workers of one search.
+struct Shared {
+    best: AtomicU32,
+    expanded: AtomicU64,
+    exhausted: AtomicBool,
+}
+
+impl<'p, S: SearchState> BranchAndBound<'p, S> {
+    /// Nodes a worker expands before reporting them to the shared counter.
+    const BUDGET_BATCH: u64 = 1024;
+
+    pub fn new(problem: &'p S::Problem) -> Self {
+        Self {
+            problem,
+            transpositions: false,
+            node_budget: None,
+            parallel_depth: 0,
+        }
+    }
+
+    /// Skip states that were already visited. Only pays off when many paths
+    /// lead to identical states, as every visited state is kept in memory.
+    /// Each parallel worker has its own table.
+    pub fn transpositions(mut self, enabled: bool) -> Self {
+        self.transpositions = enabled;
+        self
+    }
+
+    /// Stop after expanding roughly this many states.
+    pub fn node_budget(mut self, budget: u64) -> Self {
+        self.node_budget = Some(budget);
+        self
+    }
+
+    /// Expand the first `depth` levels breadth-first and search the subtrees
+    /// below them in parallel.
+    pub fn parallel_depth(mut self, depth: usize) -> Self {
+        self.parallel_depth = depth;
+        self
+    }
+
+    pub fn search(&self, root: S) -> SearchResult<S> {
+        let shared = Shared {
+            best: AtomicU32::new(root.score(self.problem)),
+            expanded: AtomicU64::new(0),
+            exhausted: AtomicBool::new(false),
+        };
+        let mut result = SearchResult {
+            best_score: shared.best.load(Ordering::Relaxed),
+            best_state: Some(root.clone()),
+            ..SearchResult::empty()
+        };
+
+        let mut frontier = vec![root];
+        let mut next = Vec::new();
+        for _ in 0..self.parallel_depth {
+            for state in frontier.drain(..) {
+                result.stats.expanded += 1;
+                state.successors(self.problem, &mut next);
+            }
+            for state in &next {
+                let score = state.score(self.problem);
+                if score > result.best_score {
+                    result.best_score = score;
+                    result.best_state = Some(state.clone());
+                }
+            }
+            std::mem::swap(&mut frontier, &mut next);
+        }
+        shared.best.fetch_max(result.best_score, Ordering::Relaxed);
+        shared
+            .expanded
+            .fetch_add(result.stats.expanded, Ordering::Relaxed);
+
+        let subtrees = if self.parallel_depth == 0 {
+            frontier
+                .into_iter()
+                .map(|state| self.search_subtree(state, &shared))
+                .fold(SearchResult::empty(), SearchResult::merge)
+        } else {
+            frontier
+                .into_par_iter()
+                .map(|state| self.search_subtree(state, &shared))
+                .reduce(SearchResult::empty, SearchResult::merge)
+        };
+        result.merge(subtrees)
+    }
+
+    fn search_subtree(&self, root: S, shared: &Shared) -> SearchResult<S> {
+        let mut result = SearchResult::empty();
+        let mut seen = HashSet::new();
+        let mut successors = Vec::new();
+        let mut stack = vec![root];
+        let mut unreported = 0;
+        while let Some(state) = stack.pop() {
+            if self.transpositions && !seen.insert(state.clone()) {
+                result.stats.transpositions += 1;
+                continue;
+            }
+            let score = state.score(self.problem);
+            if score > shared.best.fetch_max(score, Ordering::Relaxed) {
+                result.best_score = score;
+                result.best_state = Some(state.clone());
+            }
+            if state.bound(self.problem) <= shared.best.load(Ordering::Relaxed) {
+                result.stats.pruned += 1;
+                continue;
+            }
+
+            if let Some(budget) = self.node_budget {
+                let expanded = shared.expanded.load(Ordering::Relaxed) + unreported;
+                if shared.exhausted.load(Ordering::Relaxed) || expanded >= budget {
+                    shared.exhausted.store(true, Ordering::Relaxed);
+                    result.complete = false;
+                    break;
+                }
+                unreported += 1;
+                if unreported == Self::BUDGET_BATCH {
+                    shared.expanded.fetch_add(unreported, Ordering::Relaxed);
+                    unreported = 0;
+                }
+            }
+            result.stats.expanded += 1;
+            state.successors(self.problem, &mut successors);
+            // reversed so that the most promising successor is popped first
+            stack.extend(successors.drain(..).rev());
+        }
+        result
+    }
+}
+
+#[allow(unused)]
+mod test {
+    use super::*;
+
+    /// 0/1 knapsack: items are `(weight, value)`, capacity is the problem.
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    struct Knapsack {
+        next: usize,
+        weight: u32,
+        value: u32,
+    }
+
+    const ITEMS: [(u32, u32); 6] = [(5, 10), (4, 40), (6, 30), (3, 50), (2, 5), (7, 33)];
+
+    impl SearchState for Knapsack {
+        type Problem = u32;
+
+        fn score(&self, _: &u32) -> u32 {
+            self.value
+        }
+
+        fn bound(&self, _: &u32) -> u32 {
+            self.value + ITEMS[self.next..].iter().map(|i| i.1).sum::<u32>()
+        }
+
+        fn successors(&self, capacity: &u32, out: &mut Vec<Self>) {
+            let Some(&(weight, value)) = ITEMS.get(self.next) else {
+                return;
+            };
+            if self.weight + weight <= *capacity {
+                out.push(Self {
+                    next: self.next + 1,
+                    weight: self.weight + weight,
+                    value: self.value + value,
+                });
+            }
+            out.push(Self {
+                next: self.next + 1,
+                ..*self
+            });
+        }
+    }
+
+    const ROOT: Knapsack = Knapsack {
+        next: 0,
+        weight: 0,
+        value: 0,
+    };
+
+    #[test]
+    fn test_knapsack() {
+        let result = BranchAndBound::new(&10).search(ROOT);
+        assert_eq!(result.best_score, 95);
+        assert_eq!(result.best_state.unwrap().weight, 9);
+        assert!(result.complete);
+        assert!(result.stats.pruned > 0);
+
+        let parallel = BranchAndBound::new(&10)
+            .parallel_depth(2)
+            .transpositions(true)
+            .search(ROOT);
+        assert_eq!(parallel.best_score, 95);
+        assert!(parallel.complete);
+    }
+
+    #[test]
+    fn test_budget() {
+        let result = BranchAndBound::new(&10).node_budget(0).search(ROOT);
+        assert!(!result.complete);
+        assert!(result.best_score < 95);
+    }
+}
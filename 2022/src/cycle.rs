@@ -0,0 +1,174 @@
+use std::{collections::HashMap, hash::Hash};
+
+/// A sequence of states `x0, x1, ...` is periodic from step `start` on:
+/// `x(n + period) == x(n)` for all `n >= start`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cycle {
+    pub start: usize,
+    pub period: usize,
+}
+
+impl Cycle {
+    /// Step in the first repetition with the same state as step `n`.
+    pub fn equivalent_step(&self, n: usize) -> usize {
+        if n < self.start {
+            n
+        } else {
+            self.start + (n - self.start) % self.period
+        }
+    }
+
+    /// Value of a per-step metric after `n` steps, where `metrics[i]` is the
+    /// value after `i` steps and must cover at least `start + period` steps.
+    ///
+    /// Every repetition of the cycle is assumed to change the metric by the
+    /// same amount, which holds both for metrics that only depend on the
+    /// state and for running totals such as a height.
+    pub fn extrapolate(&self, metrics: &[i64], n: usize) -> i64 {
+        let end = self.start + self.period;
+        assert!(metrics.len() > end, "metrics do not cover a full cycle");
+        if n <= end {
+            return metrics[n];
+        }
+        let repetitions = ((n - self.start) / self.period) as i64;
+        let per_cycle = metrics[end] - metrics[self.start];
+        metrics[self.equivalent_step(n)] + (repetitions * per_cycle)
+    }
+}
+
+/// Cycle found by `find_cycle`, along with the metric after each step.
+#[derive(Debug, Clone)]
+pub struct CycleHistory {
+    pub cycle: Cycle,
+    pub metrics: Vec<i64>,
+}
+
+impl CycleHistory {
+    pub fn value_after(&self, n: usize) -> i64 {
+        self.cycle.extrapolate(&self.metrics, n)
+    }
+}
+
+/// Advance `state` with `step` until its fingerprint repeats, using a hash map
+/// of all fingerprints seen. Gives up after `max_steps` steps.
+///
+/// Fingerprints must identify the state as far as all future steps are
+/// concerned; `metric` is recorded after every step (and before the first).
+pub fn find_cycle<S, K: Hash + Eq>(
+    state: &mut S,
+    mut step: impl FnMut(&mut S),
+    mut fingerprint: impl FnMut(&S) -> K,
+    mut metric: impl FnMut(&S) -> i64,
+    max_steps: usize,
+) -> Option<CycleHistory> {
+    let mut seen = HashMap::new();
+    let mut metrics = Vec::new();
+    for n in 0..=max_steps {
+        metrics.push(metric(state));
+        if let Some(start) = seen.insert(fingerprint(state), n) {
+            return Some(CycleHistory {
+                cycle: Cycle {
+                    start,
+                    period: n - start,
+                },
+                metrics,
+            });
+        }
+        if n < max_steps {
+            step(state);
+        }
+    }
+    None
+}
+
+/// Brent's algorithm: finds the cycle of an eventually periodic sequence in
+/// constant memory, at the cost of stepping about three times as often as
+/// `find_cycle`. Loops forever if the sequence never repeats.
+pub fn brent<S: Clone, K: PartialEq>(
+    initial: &S,
+    mut step: impl FnMut(&S) -> S,
+    mut fingerprint: impl FnMut(&S) -> K,
+) -> Cycle {
+    // find the period by letting the hare run ahead in doubling stretches
+    let mut power = 1;
+    let mut period = 1;
+    let mut tortoise = fingerprint(initial);
+    let mut hare_state = step(initial);
+    let mut hare = fingerprint(&hare_state);
+    while tortoise != hare {
+        if power == period {
+            tortoise = hare;
+            power *= 2;
+            period = 0;
+        }
+        hare_state = step(&hare_state);
+        hare = fingerprint(&hare_state);
+        period += 1;
+    }
+
+    // the first meeting of two walkers `period` apart is the cycle start
+    let mut tortoise_state = initial.clone();
+    let mut hare_state = initial.clone();
+    for _ in 0..period {
+        hare_state = step(&hare_state);
+    }
+    let mut start = 0;
+    while fingerprint(&tortoise_state) != fingerprint(&hare_state) {
+        tortoise_state = step(&tortoise_state);
+        hare_state = step(&hare_state);
+        start += 1;
+    }
+    Cycle { start, period }
+}
+
+#[allow(unused)]
+mod test {
+    use super::*;
+
+    /// `x -> x^2 + 1 mod 255`, starting at 3: 3, 10, 101, 2, 5, 26, 167, 95,
+    /// 101, ... so the cycle starts at step 2 with period 6.
+    fn square_plus_one(x: &u32) -> u32 {
+        (x * x + 1) % 255
+    }
+
+    #[test]
+    fn test_brent() {
+        let cycle = brent(&3, square_plus_one, |&x| x);
+        assert_eq!((cycle.start, cycle.period), (2, 6));
+
+        let fixed_point = brent(&7, |_| 0, |&x| x);
+        assert_eq!((fixed_point.start, fixed_point.period), (1, 1));
+        let pure = brent(&0, |x| (x + 1) % 5, |&x| x);
+        assert_eq!((pure.start, pure.period), (0, 5));
+    }
+
+    #[test]
+    fn test_find_cycle() {
+        // running sum of the square_plus_one sequence
+        let mut state = (3, 3);
+        let history = find_cycle(
+            &mut state,
+            |(x, sum)| {
+                *x = square_plus_one(x);
+                *sum += *x as i64;
+            },
+            |&(x, _)| x,
+            |&(_, sum)| sum,
+            100,
+        )
+        .unwrap();
+        assert_eq!((history.cycle.start, history.cycle.period), (2, 6));
+
+        let mut x = 3;
+        let mut sum = 3;
+        for n in 1..=1000 {
+            x = square_plus_one(&x);
+            sum += x as i64;
+            assert_eq!(history.value_after(n), sum);
+        }
+        assert_eq!(history.value_after(0), 3);
+
+        let mut counter = 0u32;
+        assert!(find_cycle(&mut counter, |c| *c += 1, |&c| c, |&c| c as i64, 50).is_none());
+    }
+}
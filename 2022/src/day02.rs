@@ -125,75 +125,133 @@ const OPTIONS_FOR_RESULTS: &[u32] = &[
 
 const SIMD_WIDTH_BITS: usize = 512;
 const SIMD_WIDTH: usize = SIMD_WIDTH_BITS / 32;
+const LINE_LEN: usize = 4;
+const BLOCK_LEN: usize = SIMD_WIDTH * LINE_LEN;
 
-// Every line is a four-byte entry in the shape of 'A X\n'. If we interpret it as a u32,
-// we can subtract LINE_BASE to get a binary entry in the shape of '...00...11' where
-// the two bits at the respective A/X position represent numbers 0-2 for the move (or result).
-const LINE_BASE: u32 =
-    (b'A' as u32) + ((b' ' as u32) << 8) + ((b'X' as u32) << 16) + ((b'\n' as u32) << 24);
+// Every line is a four-byte entry in the shape of 'A X\n'. If we interpret it as a little-endian
+// u32, we can subtract LINE_BASE to get a binary entry in the shape of '...00...11' where the two
+// bits at the respective A/X position represent numbers 0-2 for the move (or result).
+const LINE_BASE: u32 = u32::from_le_bytes(*b"A X\n");
 
 const LINE_BASE_SIMD: Simd<u32, SIMD_WIDTH> = Simd::from_array([LINE_BASE; SIMD_WIDTH]);
 const RIGHT_SHIFT_SIMD: Simd<u32, SIMD_WIDTH> = Simd::from_array([14; SIMD_WIDTH]);
 const BIT_MASK_SIMD: Simd<u32, SIMD_WIDTH> = Simd::from_array([0b1111; SIMD_WIDTH]);
 
-#[inline(always)]
-fn simdify_entries(input: &[u8; SIMD_WIDTH_BITS / 8]) -> [u32; SIMD_WIDTH] {
-    // assuming that we are on a little-endian architecture because that's how LINE_BASE is declared
-    // SAFETY: honestly don't know why this would be unsafe. We're reinterpreting 4xu8 as 1xu32 so what could go wrong?
-    let (_, input_u32, _) = unsafe { input.align_to::<u32>() };
-    let input_vec: Simd<u32, SIMD_WIDTH> = Simd::from_slice(&input_u32);
-
-    // subtract the line base to get a binary entry in the shape of '...00...11'
-    let base_vec = input_vec - LINE_BASE_SIMD;
-    // shift the bits to the right to get a binary entry in the shape of '...0011'
-    // mask the bits to get a binary entry '0011'
-    let bitshifted = ((base_vec >> RIGHT_SHIFT_SIMD) + base_vec) & BIT_MASK_SIMD;
-
-    bitshifted.to_array()
+/// Whole input, with the trailing newline added if it is missing so that every line has
+/// `LINE_LEN` bytes.
+fn read_lines(input: &mut dyn BufRead) -> Vec<u8> {
+    let mut lines = Vec::new();
+    input.read_to_end(&mut lines).unwrap();
+    if lines.last().is_some_and(|&b| b != b'\n') {
+        lines.push(b'\n');
+    }
+    assert!(lines.len() % LINE_LEN == 0, "lines must look like 'A X'");
+    lines
 }
 
-#[target_feature(enable = "avx2")]
-unsafe fn part1_play_strategy_avx(input: &mut dyn BufRead) -> String {
+fn score_scalar(lines: &[u8], table: &[u32]) -> u64 {
+    lines
+        .chunks_exact(LINE_LEN)
+        .map(|line| {
+            let base = u32::from_le_bytes(line.try_into().unwrap()).wrapping_sub(LINE_BASE);
+            let idx = ((base >> 14) + base) & 0b1111;
+            table[idx as usize] as u64
+        })
+        .sum()
+}
+
+#[inline(always)]
+fn score_simd(lines: &[u8], table: &[u32]) -> u64 {
+    let blocks = lines.chunks_exact(BLOCK_LEN);
+    let tail = blocks.remainder();
     let mut total_score = 0;
-    let mut entry = [0u8; SIMD_WIDTH_BITS / 8];
-    loop {
-        let bytes_read = input.read(&mut entry).unwrap();
-        if bytes_read == 0 {
-            break;
-        }
-        let arr = simdify_entries(&entry);
-        for i in 0..(bytes_read / 4) {
-            total_score += OPTIONS_FOR_MOVES[arr[i] as usize] as u64;
+    for block in blocks {
+        let input_vec = Simd::<u32, SIMD_WIDTH>::from_array(std::array::from_fn(|i| {
+            u32::from_le_bytes(block[i * LINE_LEN..][..LINE_LEN].try_into().unwrap())
+        }));
+        // subtract the line base to get a binary entry in the shape of '...00...11'
+        let base_vec = input_vec - LINE_BASE_SIMD;
+        // shift the bits to the right to get a binary entry in the shape of '...0011'
+        // mask the bits to get a binary entry '0011'
+        let bitshifted = ((base_vec >> RIGHT_SHIFT_SIMD) + base_vec) & BIT_MASK_SIMD;
+        for idx in bitshifted.to_array() {
+            total_score += table[idx as usize] as u64;
         }
     }
-    total_score.to_string()
+    total_score + score_scalar(tail, table)
 }
 
-fn part1_play_strategy(input: &mut dyn BufRead) -> String {
-    // SAFETY: my PC has avx2
-    unsafe { part1_play_strategy_avx(input) }
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn score_avx2(lines: &[u8], table: &[u32]) -> u64 {
+    score_simd(lines, table)
 }
 
-#[target_feature(enable = "avx2")]
-unsafe fn part2_play_to_result_avx(input: &mut dyn BufRead) -> String {
-    let mut total_score = 0;
-    let mut entry = [0u8; SIMD_WIDTH_BITS / 8];
-    loop {
-        let bytes_read = input.read(&mut entry).unwrap();
-        if bytes_read == 0 {
-            break;
-        }
-        let arr = simdify_entries(&entry);
-        for i in 0..(bytes_read / 4) {
-            total_score += OPTIONS_FOR_RESULTS[arr[i] as usize] as u64;
-        }
+/// Sum of `table` over all lines, with the fastest implementation this CPU supports.
+fn score(lines: &[u8], table: &[u32]) -> u64 {
+    if lines.len() < BLOCK_LEN {
+        return score_scalar(lines, table);
+    }
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    if is_x86_feature_detected!("avx2") {
+        // SAFETY: we just checked that the CPU has avx2
+        return unsafe { score_avx2(lines, table) };
     }
-    total_score.to_string()
+    score_simd(lines, table)
+}
+
+fn part1_play_strategy(input: &mut dyn BufRead) -> String {
+    score(&read_lines(input), OPTIONS_FOR_MOVES).to_string()
 }
 
 fn part2_play_to_result(input: &mut dyn BufRead) -> String {
-    // SAFETY: my PC has avx2
-    unsafe { part2_play_to_result_avx(input) }
+    score(&read_lines(input), OPTIONS_FOR_RESULTS).to_string()
 }
 
 pub const SOLVERS: &[Solver] = &[part1_play_strategy, part2_play_to_result];
+
+#[allow(unused)]
+mod test {
+    use super::*;
+
+    /// Every combination of moves a few times over, in a scrambled order.
+    fn sample_lines(count: usize) -> Vec<u8> {
+        (0..count)
+            .flat_map(|i| {
+                let combo = i * 7 % 9;
+                [b'A' + (combo % 3) as u8, b' ', b'X' + (combo / 3) as u8, b'\n']
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_example() {
+        let example = b"A Y\nB X\nC Z";
+        assert_eq!(part1_play_strategy(&mut &example[..]), "15");
+        assert_eq!(part2_play_to_result(&mut &example[..]), "12");
+    }
+
+    #[test]
+    fn test_paths_agree() {
+        for count in [0, 1, 15, 16, 17, 33, 100] {
+            let lines = sample_lines(count);
+            for table in [OPTIONS_FOR_MOVES, OPTIONS_FOR_RESULTS] {
+                let expected = score_scalar(&lines, table);
+                assert_eq!(score_simd(&lines, table), expected);
+                assert_eq!(score(&lines, table), expected);
+                #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+                if is_x86_feature_detected!("avx2") {
+                    assert_eq!(unsafe { score_avx2(&lines, table) }, expected);
+                }
+            }
+
+            // the same input without the final newline
+            let mut unterminated = lines.clone();
+            unterminated.pop();
+            assert_eq!(
+                part1_play_strategy(&mut &unterminated[..]),
+                score_scalar(&lines, OPTIONS_FOR_MOVES).to_string()
+            );
+        }
+    }
+}
@@ -1,113 +1,298 @@
-use std::io::BufRead;
+use std::{
+    collections::HashMap,
+    io::{BufRead, Write},
+};
 
 use bstr::io::BufReadExt;
 
-use crate::{parse_nums, Solver};
+use crate::{scan::Pattern, Solver};
 
-pub const STACKS_MAX: usize = 9;
+/// Label of a crate, without the brackets. Usually a single letter.
+type Crate = Vec<u8>;
 
-struct Stacks([Vec<u8>; STACKS_MAX]);
-
-impl Stacks {
-    pub fn new() -> Self {
-        Self(Default::default())
-    }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Crane {
+    /// Moves one crate at a time, reversing their order.
+    Lifo,
+    /// Moves all crates at once, keeping their order.
+    Fifo,
+}
 
-    pub fn feed(&mut self, line: &[u8]) {
-        assert!(line.len() + 1 <= STACKS_MAX * 4);
-        let stacks_on_line = (line.len() + 1) / 4;
-        for i in 0..stacks_on_line {
-            let char = line[i * 4 + 1];
-            if char != b' ' {
-                self.0[i].push(char);
+/// Start and end column of every run of non-space bytes in `line`.
+fn tokens(line: &[u8]) -> Vec<(usize, usize)> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (col, &byte) in line.iter().chain(b" ").enumerate() {
+        match (start, byte == b' ') {
+            (None, false) => start = Some(col),
+            (Some(s), true) => {
+                tokens.push((s, col));
+                start = None;
             }
+            _ => {}
         }
     }
+    tokens
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stacks {
+    labels: Vec<Vec<u8>>,
+    /// Index of the stack with each label.
+    index: HashMap<Vec<u8>, usize>,
+    stacks: Vec<Vec<Crate>>,
+}
+
+impl Stacks {
+    /// Read the drawing up to the blank line that separates it from the moves.
+    /// Stacks are found from the label row at the bottom, and every crate
+    /// belongs to the stack whose label is below it.
+    pub fn load(mut input: &mut dyn BufRead) -> Self {
+        let mut drawing = Vec::new();
+        input
+            .for_byte_line(|line| {
+                Ok(if line.is_empty() {
+                    false
+                } else {
+                    drawing.push(line.to_vec());
+                    true
+                })
+            })
+            .unwrap();
+        let label_row = drawing.pop().expect("missing stack labels");
+        let label_spans = tokens(&label_row);
 
-    pub fn prepare(&mut self) {
-        for stack in self.0.iter_mut() {
-            stack.reverse();
+        let mut stacks = vec![Vec::new(); label_spans.len()];
+        for line in drawing.iter().rev() {
+            for (start, end) in tokens(line) {
+                let token = &line[start..end];
+                assert!(
+                    token.len() > 2 && token[0] == b'[' && token[token.len() - 1] == b']',
+                    "invalid crate {:?}",
+                    String::from_utf8_lossy(token)
+                );
+                let mut below = label_spans
+                    .iter()
+                    .enumerate()
+                    .filter(|&(_, &(l_start, l_end))| l_start < end && start < l_end)
+                    .map(|(stack, _)| stack);
+                let stack = below.next().expect("crate without a stack below");
+                assert!(
+                    below.next().is_none(),
+                    "crate {:?} is above more than one stack",
+                    String::from_utf8_lossy(token)
+                );
+                stacks[stack].push(token[1..token.len() - 1].to_vec());
+            }
+        }
+        let labels = label_spans
+            .into_iter()
+            .map(|(start, end)| label_row[start..end].to_vec())
+            .collect::<Vec<_>>();
+        let index = labels
+            .iter()
+            .enumerate()
+            .map(|(stack, label)| (label.clone(), stack))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(index.len(), labels.len(), "duplicate stack labels");
+        Self {
+            labels,
+            index,
+            stacks,
         }
     }
 
-    pub fn move_items_lifo(&mut self, count: usize, from: usize, to: usize) {
-        let len = self.0[from].len();
-        assert!(count <= len);
-        for idx in 0..count {
-            self.0[to].push(self.0[from][len - idx - 1]);
-        }
-        self.0[from].truncate(len - count);
+    /// Index of the stack labeled `label`.
+    pub fn stack(&self, label: &[u8]) -> usize {
+        *self
+            .index
+            .get(label)
+            .unwrap_or_else(|| panic!("no stack {:?}", String::from_utf8_lossy(label)))
     }
 
-    pub fn move_items_fifo(&mut self, count: usize, from: usize, to: usize) {
+    fn two_stacks(&mut self, from: usize, to: usize) -> (&mut Vec<Crate>, &mut Vec<Crate>) {
+        assert_ne!(from, to, "moving crates onto their own stack");
         let pivot = from.min(to) + 1;
-        let (left, right) = self.0.split_at_mut(pivot);
-        let (from, to) = if from < to {
+        let (left, right) = self.stacks.split_at_mut(pivot);
+        if from < to {
             (&mut left[from], &mut right[to - pivot])
         } else {
             (&mut right[from - pivot], &mut left[to])
-        };
+        }
+    }
+
+    pub fn move_items_lifo(&mut self, count: usize, from: usize, to: usize) {
+        let (from, to) = self.two_stacks(from, to);
+        let iter = from.drain((from.len() - count)..).rev();
+        to.extend(iter);
+    }
+
+    pub fn move_items_fifo(&mut self, count: usize, from: usize, to: usize) {
+        let (from, to) = self.two_stacks(from, to);
         let iter = from.drain((from.len() - count)..);
         to.extend(iter);
     }
 
-    pub fn tops(&self) -> impl Iterator<Item = u8> + '_ {
-        self.0.iter().flat_map(|stack| stack.last().copied())
+    pub fn move_items(&mut self, crane: Crane, count: usize, from: usize, to: usize) {
+        match crane {
+            Crane::Lifo => self.move_items_lifo(count, from, to),
+            Crane::Fifo => self.move_items_fifo(count, from, to),
+        }
     }
-}
 
-fn part1_move_stacks(mut input: &mut dyn BufRead) -> String {
-    let mut stacks = Stacks::new();
-    input
-        .for_byte_line(|line| {
-            Ok(if line.is_empty() {
-                false
-            } else {
-                stacks.feed(line);
-                true
-            })
-        })
-        .unwrap();
-    stacks.prepare();
+    pub fn tops(&self) -> impl Iterator<Item = &[u8]> + '_ {
+        self.stacks
+            .iter()
+            .flat_map(|stack| stack.last().map(|c| c.as_slice()))
+    }
 
-    let mut numbers = [0u64; 3];
-    input
-        .for_byte_line(|line| {
-            parse_nums(line, &mut numbers);
-            let [count, from, to] = numbers;
-            stacks.move_items_lifo(count as usize, (from - 1) as usize, (to - 1) as usize);
-            Ok(true)
-        })
-        .unwrap();
+    /// Draw the stacks in the layout of the input, with every column as wide
+    /// as the widest crate or label.
+    pub fn render(&self) -> String {
+        let width = self
+            .stacks
+            .iter()
+            .flatten()
+            .map(|c| c.len() + 2)
+            .chain(self.labels.iter().map(|l| l.len()))
+            .max()
+            .unwrap_or(0);
+        let height = self.stacks.iter().map(Vec::len).max().unwrap_or(0);
 
-    stacks.tops().map(|c| c as char).collect()
-}
+        let mut rows = Vec::with_capacity(height + 1);
+        for level in (0..height).rev() {
+            let cells = self.stacks.iter().map(|stack| match stack.get(level) {
+                Some(item) => {
+                    let item = format!("[{}]", String::from_utf8_lossy(item));
+                    format!("{:<width$}", item, width = width)
+                }
+                None => " ".repeat(width),
+            });
+            rows.push(cells.collect::<Vec<_>>().join(" "));
+        }
+        let labels = self.labels.iter().map(|label| {
+            let label = String::from_utf8_lossy(label);
+            format!("{:^width$}", label, width = width)
+        });
+        rows.push(labels.collect::<Vec<_>>().join(" "));
 
-fn part2_mover9001(mut input: &mut dyn BufRead) -> String {
-    let mut stacks = Stacks::new();
-    input
-        .for_byte_line(|line| {
-            Ok(if line.is_empty() {
-                false
-            } else {
-                stacks.feed(line);
-                true
-            })
-        })
-        .unwrap();
-    stacks.prepare();
+        let mut out = rows.join("\n");
+        out.push('\n');
+        out
+    }
+}
 
-    let mut numbers = [0u64; 3];
+/// Rearrange the stacks from the input with `crane`, writing the drawing
+/// after every move to `trace`, and return the crates on top.
+pub fn rearrange(
+    mut input: &mut dyn BufRead,
+    crane: Crane,
+    mut trace: Option<&mut dyn Write>,
+) -> String {
+    let mut stacks = Stacks::load(input);
+    let pattern = Pattern::new("move {count} from {from} to {to}");
+    if let Some(trace) = trace.as_deref_mut() {
+        writeln!(trace, "{}", stacks.render()).unwrap();
+    }
     input
         .for_byte_line(|line| {
-            parse_nums(line, &mut numbers);
-            let [count, from, to] = numbers;
-            stacks.move_items_fifo(count as usize, (from - 1) as usize, (to - 1) as usize);
+            let (count, from, to): (usize, &[u8], &[u8]) = pattern.scan(line).unwrap();
+            let (from, to) = (stacks.stack(from), stacks.stack(to));
+            stacks.move_items(crane, count, from, to);
+            if let Some(trace) = trace.as_deref_mut() {
+                writeln!(
+                    trace,
+                    "{}\n{}",
+                    String::from_utf8_lossy(line),
+                    stacks.render()
+                )?;
+            }
             Ok(true)
         })
         .unwrap();
 
-    stacks.tops().map(|c| c as char).collect()
+    stacks
+        .tops()
+        .map(|c| String::from_utf8_lossy(c).into_owned())
+        .collect()
+}
+
+fn part1_move_stacks(input: &mut dyn BufRead) -> String {
+    rearrange(input, Crane::Lifo, None)
+}
+
+fn part2_mover9001(input: &mut dyn BufRead) -> String {
+    rearrange(input, Crane::Fifo, None)
 }
 
 pub const SOLVERS: &[Solver] = &[part1_move_stacks, part2_mover9001];
+
+#[allow(unused)]
+mod test {
+    use super::*;
+
+    const DRAWING: &str = "    [D]    \n[N] [C]    \n[Z] [M] [P]\n 1   2   3 \n";
+    const MOVES: &str =
+        "move 1 from 2 to 1\nmove 3 from 1 to 3\nmove 2 from 2 to 1\nmove 1 from 1 to 2\n";
+
+    #[test]
+    fn test_example() {
+        let input = format!("{}\n{}", DRAWING, MOVES);
+        assert_eq!(part1_move_stacks(&mut input.as_bytes()), "CMZ");
+        assert_eq!(part2_mover9001(&mut input.as_bytes()), "MCD");
+
+        let stacks = Stacks::load(&mut input.as_bytes());
+        assert_eq!(stacks.render(), DRAWING);
+    }
+
+    #[test]
+    fn test_trace() {
+        let input = format!("{}\n{}", DRAWING, MOVES);
+        let mut trace = Vec::new();
+        rearrange(&mut input.as_bytes(), Crane::Lifo, Some(&mut trace));
+        let trace = String::from_utf8(trace).unwrap();
+        let frames = trace.split("\n\n").collect::<Vec<_>>();
+        assert_eq!(Some(frames[0]), DRAWING.strip_suffix('\n'));
+        assert_eq!(
+            frames[1],
+            "move 1 from 2 to 1\n[D]        \n[N] [C]    \n[Z] [M] [P]\n 1   2   3 "
+        );
+        assert_eq!(
+            frames[3],
+            "move 2 from 2 to 1\n        [Z]\n        [N]\n[M]     [D]\n[C]     [P]\n 1   2   3 "
+        );
+        assert_eq!(
+            frames[4],
+            "move 1 from 1 to 2\n        [Z]\n        [N]\n        [D]\n[C] [M] [P]\n 1   2   3 "
+        );
+
+        let mut fifo = Vec::new();
+        rearrange(&mut input.as_bytes(), Crane::Fifo, Some(&mut fifo));
+        assert!(String::from_utf8(fifo)
+            .unwrap()
+            .ends_with("        [D]\n        [N]\n        [Z]\n[M] [C] [P]\n 1   2   3 \n\n"));
+    }
+
+    #[test]
+    fn test_wide_labels() {
+        let drawing = "\
+[AB]                                             [XY]
+[CD] [EF]                                        [Z]
+ 1    2    3    4    5    6    7    8    9    10   11
+";
+        let input = format!("{}\nmove 2 from 1 to 11\nmove 1 from 11 to 3\n", drawing);
+        let mut stacks = Stacks::load(&mut input.as_bytes());
+        assert_eq!(stacks.stacks.len(), 11);
+        assert_eq!(stacks.stacks[10], [b"Z".to_vec(), b"XY".to_vec()]);
+        assert_eq!(
+            rearrange(&mut input.as_bytes(), Crane::Lifo, None),
+            "EFCDAB"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "above more than one stack")]
+    fn test_ambiguous_crate() {
+        Stacks::load(&mut &b"[ABCDE]\n 1   2 \n\n"[..]);
+    }
+}
@@ -1,56 +1,199 @@
-use std::io::BufRead;
+use std::{
+    collections::VecDeque,
+    io::{BufRead, Bytes, Read},
+};
 
 use crate::Solver;
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
-struct AsciiLowerBitSet (u32);
+/// How often every byte value occurs in a window, kept up to date as bytes
+/// enter and leave it.
+#[derive(Debug, Clone)]
+pub struct ByteCounts {
+    counts: [u32; 256],
+    /// Byte values that occur more than once.
+    repeated: usize,
+}
 
-impl AsciiLowerBitSet {
+impl ByteCounts {
     pub fn new() -> Self {
-        Self(0)
+        Self {
+            counts: [0; 256],
+            repeated: 0,
+        }
     }
 
-    pub fn add(&mut self, c: u8) {
-        //assert!(b'a' <= c && c <= b'z');
-        self.0 |= 1 << (c - b'a');
+    /// Count `byte` once more, and tell whether all counted bytes are
+    /// different.
+    pub fn add(&mut self, byte: u8) -> bool {
+        let count = &mut self.counts[byte as usize];
+        *count += 1;
+        if *count == 2 {
+            self.repeated += 1;
+        }
+        self.repeated == 0
     }
 
-    pub fn contains(&self, c: u8) -> bool {
-        //assert!(b'a' <= c && c <= b'z');
-        self.0 & (1 << (c - b'a')) != 0
+    /// Count `byte` once less, and tell whether all counted bytes are
+    /// different.
+    pub fn remove(&mut self, byte: u8) -> bool {
+        let count = &mut self.counts[byte as usize];
+        assert!(*count > 0, "removing uncounted byte {}", byte);
+        if *count == 2 {
+            self.repeated -= 1;
+        }
+        *count -= 1;
+        self.repeated == 0
     }
+}
 
-    pub fn remove(&mut self, c: u8) {
-        //assert!(b'a' <= c && c <= b'z');
-        self.0 &= !(1 << (c - b'a'));
+impl Default for ByteCounts {
+    fn default() -> Self {
+        Self::new()
     }
+}
+
+/// Sliding window over a byte stream that knows whether its bytes are all
+/// different, by counting every byte value in it.
+#[derive(Debug, Clone)]
+pub struct DistinctWindow {
+    size: usize,
+    window: VecDeque<u8>,
+    counts: ByteCounts,
+}
 
-    #[allow(unused)]
-    pub fn len(&self) -> usize {
-        self.0.count_ones() as usize
+impl DistinctWindow {
+    pub fn new(size: usize) -> Self {
+        assert!(size > 0, "empty window");
+        Self {
+            size,
+            window: VecDeque::with_capacity(size),
+            counts: ByteCounts::new(),
+        }
+    }
+
+    /// Slide the window forward by `byte`, and tell whether the window is
+    /// full and all of its bytes are different.
+    pub fn push(&mut self, byte: u8) -> bool {
+        if self.window.len() == self.size {
+            let old = self.window.pop_front().unwrap();
+            self.counts.remove(old);
+        }
+        self.window.push_back(byte);
+        let distinct = self.counts.add(byte);
+        self.window.len() == self.size && distinct
     }
 }
 
-fn find_distinct_prefix_n<const N: usize>(input: &mut dyn BufRead) -> String {
-    let mut data = vec![];
-    input.read_to_end(&mut data).unwrap();
-    let mut window_start = 0;
-    let mut set = AsciiLowerBitSet::new();
-    for i in 0..data.len() {
-        if !set.contains(data[i]) {
-            set.add(data[i])
-        } else {
-            while data[window_start] != data[i] {
-                set.remove(data[window_start]);
-                window_start += 1;
+/// Positions after which the last `size` bytes were all different, counted
+/// in bytes read.
+pub struct Markers<'r> {
+    bytes: Bytes<&'r mut dyn BufRead>,
+    window: DistinctWindow,
+    position: usize,
+}
+
+impl<'r> Markers<'r> {
+    pub fn new(input: &'r mut dyn BufRead, size: usize) -> Self {
+        Self {
+            bytes: input.bytes(),
+            window: DistinctWindow::new(size),
+            position: 0,
+        }
+    }
+}
+
+impl Iterator for Markers<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        for byte in self.bytes.by_ref() {
+            self.position += 1;
+            if self.window.push(byte.unwrap()) {
+                return Some(self.position);
             }
-            window_start += 1;
         }
-        if i - window_start + 1 == N {
-            return i.to_string()
+        None
+    }
+}
+
+/// Bytes read until the last `size` of them were all different.
+pub fn first_marker(input: &mut dyn BufRead, size: usize) -> Option<usize> {
+    Markers::new(input, size).next()
+}
+
+/// Bytes read until the last `N` of them were all different, keeping the
+/// window in a ring buffer of fixed size.
+pub fn first_marker_n<const N: usize>(input: &mut dyn BufRead) -> Option<usize> {
+    assert!(N > 0, "empty window");
+    let mut ring = [0u8; N];
+    let mut counts = ByteCounts::new();
+    for (pos, byte) in input.bytes().enumerate() {
+        let byte = byte.unwrap();
+        let slot = &mut ring[pos % N];
+        if pos >= N {
+            counts.remove(*slot);
+        }
+        *slot = byte;
+        if counts.add(byte) && pos + 1 >= N {
+            return Some(pos + 1);
         }
     }
-    unreachable!()
+    None
+}
+
+fn find_distinct_prefix_n<const N: usize>(input: &mut dyn BufRead) -> String {
+    first_marker_n::<N>(input).expect("no marker").to_string()
 }
 
 pub const SOLVERS: &[Solver] = &[find_distinct_prefix_n::<4>, find_distinct_prefix_n::<14>];
+
+#[allow(unused)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_examples() {
+        for (input, packet, message) in [
+            ("mjqjpqmgbljsphdztnvjfqwrcgsmlb", "7", "19"),
+            ("bvwbjplbgvbhsrlpgdmjqwftvncz", "5", "23"),
+            ("nppdvjthqldpwncqszvftbrmjlhg", "6", "23"),
+            ("nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg", "10", "29"),
+            ("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw", "11", "26"),
+        ] {
+            assert_eq!(SOLVERS[0](&mut input.as_bytes()), packet);
+            assert_eq!(SOLVERS[1](&mut input.as_bytes()), message);
+        }
+    }
+
+    #[test]
+    fn test_any_bytes() {
+        let mut input = &[0, 255, 0, 0, 128, 7, 255, 255][..];
+        assert_eq!(Markers::new(&mut input, 3).collect::<Vec<_>>(), [6, 7]);
+        assert_eq!(first_marker(&mut &b"aaaa"[..], 2), None);
+        assert_eq!(first_marker(&mut &b"a"[..], 1), Some(1));
+        assert_eq!(first_marker(&mut &b"abc"[..], 4), None);
+
+        assert_eq!(
+            first_marker_n::<3>(&mut &[0, 255, 0, 0, 128, 7][..]),
+            Some(6)
+        );
+        assert_eq!(first_marker_n::<2>(&mut &b"aaaa"[..]), None);
+        assert_eq!(first_marker_n::<1>(&mut &b"a"[..]), Some(1));
+        assert_eq!(first_marker_n::<4>(&mut &b"abc"[..]), None);
+        for text in [
+            "abcabcdabcde",
+            "zzzzyzyxzyxwzyxwv",
+            "mjqjpqmgbljsphdztnvjfqwrcgsmlb",
+        ] {
+            let runtime = (1..=5).map(|n| first_marker(&mut text.as_bytes(), n));
+            let fixed = [
+                first_marker_n::<1>(&mut text.as_bytes()),
+                first_marker_n::<2>(&mut text.as_bytes()),
+                first_marker_n::<3>(&mut text.as_bytes()),
+                first_marker_n::<4>(&mut text.as_bytes()),
+                first_marker_n::<5>(&mut text.as_bytes()),
+            ];
+            assert!(runtime.eq(fixed), "{}", text);
+        }
+    }
+}
@@ -1,56 +1,158 @@
-use std::io::BufRead;
+use std::{collections::BTreeMap, fmt::Write, io::BufRead};
 
-use bstr::io::BufReadExt;
+use bstr::{io::BufReadExt, ByteSlice};
 
 use crate::{parse_num, Solver};
 
-const MAX_DIRS: usize = 1000;
-
 const DIRSIZE_LIMIT: u64 = 100_000;
 
 const DISK_SPACE: u64 = 70_000_000;
 const SPACE_REQUIRED: u64 = 30_000_000;
 
-fn dirscan(mut input: &mut dyn BufRead) -> Vec<u64> {
-    let mut dirsizes = Vec::with_capacity(MAX_DIRS);
-    let mut stack = Vec::with_capacity(100);
-    let mut top = 0;
-    input
-        .for_byte_line(|line| {
-            match line[0] {
-                b'$' => {
-                    if line.len() >= 6 {
-                        // '$ cd ?'
-                        match line[5] {
-                            b'.' => {
-                                // '$ cd ..'
-                                dirsizes.push(top);
-                                top += stack.pop().unwrap();
-                            }
-                            _ => {
-                                // '$ cd [dirname]'
-                                stack.push(top);
-                                top = 0;
-                            }
-                        }
-                    }
-                    // '$ ls' is ignored
+pub type DirId = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Entry {
+    Dir(DirId),
+    File(u64),
+}
+
+#[derive(Debug, Clone, Default)]
+struct Dir {
+    parent: Option<DirId>,
+    entries: BTreeMap<String, Entry>,
+}
+
+/// Directory tree rebuilt from a terminal transcript. Directories are stored
+/// in the order they were discovered, so parents come before their children.
+#[derive(Debug, Clone)]
+pub struct FileSystem {
+    dirs: Vec<Dir>,
+}
+
+impl FileSystem {
+    pub const ROOT: DirId = 0;
+
+    pub fn new() -> Self {
+        Self {
+            dirs: vec![Dir::default()],
+        }
+    }
+
+    pub fn load(input: &mut dyn BufRead) -> Self {
+        let mut fs = Self::new();
+        let mut cwd = Self::ROOT;
+        for line in input.byte_lines() {
+            let line = line.unwrap();
+            if let Some(target) = line.strip_prefix(b"$ cd ") {
+                cwd = match target {
+                    b"/" => Self::ROOT,
+                    b".." => fs.dirs[cwd].parent.expect("cd .. from the root"),
+                    name => fs.subdir(cwd, &name.to_str_lossy()),
+                };
+            } else if line.starts_with(b"$ ls") {
+                // the listing follows; listing the same directory again only
+                // overwrites entries with the same values
+            } else if let Some(name) = line.strip_prefix(b"dir ") {
+                fs.subdir(cwd, &name.to_str_lossy());
+            } else {
+                let (size, name) = line.split_once_str(" ").expect("invalid file line");
+                fs.add_file(cwd, &name.to_str_lossy(), parse_num(size));
+            }
+        }
+        fs
+    }
+
+    /// The subdirectory `name` of `dir`, created if it is not known yet.
+    pub fn subdir(&mut self, dir: DirId, name: &str) -> DirId {
+        match self.dirs[dir].entries.get(name) {
+            Some(&Entry::Dir(id)) => id,
+            Some(Entry::File(_)) => panic!("{} is a file", name),
+            None => {
+                let id = self.dirs.len();
+                self.dirs.push(Dir {
+                    parent: Some(dir),
+                    entries: BTreeMap::new(),
+                });
+                self.dirs[dir]
+                    .entries
+                    .insert(name.to_string(), Entry::Dir(id));
+                id
+            }
+        }
+    }
+
+    pub fn add_file(&mut self, dir: DirId, name: &str, size: u64) {
+        let previous = self.dirs[dir]
+            .entries
+            .insert(name.to_string(), Entry::File(size));
+        assert!(
+            !matches!(previous, Some(Entry::Dir(_))),
+            "{} is a directory",
+            name
+        );
+    }
+
+    pub fn entries(&self, dir: DirId) -> impl Iterator<Item = (&str, Entry)> {
+        self.dirs[dir]
+            .entries
+            .iter()
+            .map(|(name, &entry)| (name.as_str(), entry))
+    }
+
+    /// Total size of every directory including its subdirectories, indexed
+    /// by `DirId`.
+    pub fn sizes(&self) -> Vec<u64> {
+        let mut sizes = vec![0; self.dirs.len()];
+        // children come after their parents, so going backwards every
+        // directory is complete before it is added to its parent
+        for (id, dir) in self.dirs.iter().enumerate().rev() {
+            sizes[id] += dir
+                .entries
+                .values()
+                .map(|entry| match entry {
+                    Entry::File(size) => *size,
+                    Entry::Dir(_) => 0,
+                })
+                .sum::<u64>();
+            if let Some(parent) = dir.parent {
+                sizes[parent] += sizes[id];
+            }
+        }
+        sizes
+    }
+
+    /// The hierarchy in the style of the puzzle description.
+    pub fn tree(&self) -> String {
+        let mut out = String::from("- / (dir)\n");
+        self.write_tree(&mut out, Self::ROOT, 1);
+        out
+    }
+
+    fn write_tree(&self, out: &mut String, dir: DirId, depth: usize) {
+        for (name, entry) in self.entries(dir) {
+            let indent = "  ".repeat(depth);
+            match entry {
+                Entry::Dir(id) => {
+                    writeln!(out, "{}- {} (dir)", indent, name).unwrap();
+                    self.write_tree(out, id, depth + 1);
+                }
+                Entry::File(size) => {
+                    writeln!(out, "{}- {} (file, size={})", indent, name, size).unwrap();
                 }
-                b'd' => {} // dir [something] -> ignore
-                _ => top += parse_num(&line),
             }
-            Ok(true)
-        })
-        .unwrap();
-    while let Some(stack_top) = stack.pop() {
-        dirsizes.push(top);
-        top += stack_top;
+        }
+    }
+}
+
+impl Default for FileSystem {
+    fn default() -> Self {
+        Self::new()
     }
-    dirsizes
 }
 
 fn part1_dirscan(input: &mut dyn BufRead) -> String {
-    let dirsizes = dirscan(input);
+    let dirsizes = FileSystem::load(input).sizes();
     let mut total = 0;
     for size in dirsizes {
         if size <= DIRSIZE_LIMIT {
@@ -61,11 +163,11 @@ fn part1_dirscan(input: &mut dyn BufRead) -> String {
 }
 
 fn part2_identify_dir_to_delete(input: &mut dyn BufRead) -> String {
-    let dirsizes = dirscan(input);
-    let total = dirsizes.last().unwrap(); // size of the '/' directory
+    let dirsizes = FileSystem::load(input).sizes();
+    let total = dirsizes[FileSystem::ROOT];
 
     let space_left = DISK_SPACE - total;
-    let space_required = SPACE_REQUIRED - space_left;
+    let space_required = SPACE_REQUIRED.saturating_sub(space_left);
 
     let mut best_dirsize = u64::MAX;
     for size in dirsizes {
@@ -77,3 +179,75 @@ fn part2_identify_dir_to_delete(input: &mut dyn BufRead) -> String {
 }
 
 pub const SOLVERS: &[Solver] = &[part1_dirscan, part2_identify_dir_to_delete];
+
+#[allow(unused)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &str = "\
+$ cd /
+$ ls
+dir a
+14848514 b.txt
+8504156 c.dat
+dir d
+$ cd a
+$ ls
+dir e
+29116 f
+2557 g
+62596 h.lst
+$ cd e
+$ ls
+584 i
+$ cd ..
+$ cd ..
+$ cd d
+$ ls
+4060174 j
+8033020 d.log
+5626152 d.ext
+7214296 k
+";
+
+    #[test]
+    fn test_example() {
+        let fs = FileSystem::load(&mut EXAMPLE.as_bytes());
+        assert_eq!(
+            fs.tree(),
+            "\
+- / (dir)
+  - a (dir)
+    - e (dir)
+      - i (file, size=584)
+    - f (file, size=29116)
+    - g (file, size=2557)
+    - h.lst (file, size=62596)
+  - b.txt (file, size=14848514)
+  - c.dat (file, size=8504156)
+  - d (dir)
+    - d.ext (file, size=5626152)
+    - d.log (file, size=8033020)
+    - j (file, size=4060174)
+    - k (file, size=7214296)
+"
+        );
+        assert_eq!(part1_dirscan(&mut EXAMPLE.as_bytes()), "95437");
+        assert_eq!(
+            part2_identify_dir_to_delete(&mut EXAMPLE.as_bytes()),
+            "24933642"
+        );
+    }
+
+    #[test]
+    fn test_revisits() {
+        // the same transcript with jumps back to the root and repeated listings
+        let revisiting = EXAMPLE.replace(
+            "$ cd ..\n$ cd ..\n",
+            "$ cd /\n$ ls\ndir a\n$ cd a\n$ ls\n29116 f\n$ cd /\n",
+        );
+        let fs = FileSystem::load(&mut revisiting.as_bytes());
+        assert_eq!(fs.tree(), FileSystem::load(&mut EXAMPLE.as_bytes()).tree());
+        assert_eq!(fs.sizes(), [48381165, 94853, 24933642, 584]);
+    }
+}
@@ -2,9 +2,6 @@ use std::io::BufRead;
 
 use crate::{tiles::Tiles, Solver};
 
-const FOREST_DIMENSION: usize = 100;
-const MAP_SIZE: usize = FOREST_DIMENSION * (FOREST_DIMENSION + 1);
-
 fn line_visibility(
     trees: &Tiles<u8>,
     visibility: &mut Tiles<u8>,
@@ -28,7 +25,7 @@ fn line_visibility(
 }
 
 fn part1_count_visible_trees(input: &mut dyn BufRead) -> String {
-    let trees = Tiles::load(input, MAP_SIZE);
+    let trees = Tiles::load(input, 0);
     let mut visibility = Tiles::new(trees.width(), trees.height(), 0u8);
 
     for line in trees.rows_steppers().chain(trees.col_steppers()) {
@@ -50,8 +47,49 @@ fn part1_count_visible_trees(input: &mut dyn BufRead) -> String {
         .to_string()
 }
 
+/// Multiply each tree's score by how far it can see towards the start of the
+/// line, keeping the trees that could still block the view on a stack of
+/// decreasing heights.
+fn line_view_distances(
+    trees: &Tiles<u8>,
+    scores: &mut Tiles<u32>,
+    stepper: impl Iterator<Item = usize>,
+    stack: &mut Vec<(usize, u8)>,
+) {
+    stack.clear();
+    for (pos, idx) in stepper.enumerate() {
+        let tree = trees.entries[idx];
+        while stack.last().is_some_and(|&(_, blocker)| blocker < tree) {
+            stack.pop();
+        }
+        let distance = stack.last().map_or(pos, |&(blocker_pos, _)| pos - blocker_pos);
+        scores.entries[idx] *= distance as u32;
+        stack.push((pos, tree));
+    }
+}
+
+fn part2_monotonic_stack(input: &mut dyn BufRead) -> String {
+    let trees = Tiles::load(input, 0);
+    let mut scores = Tiles::new(trees.width(), trees.height(), 1u32);
+    let mut stack = Vec::with_capacity(trees.width().max(trees.height()));
+
+    for line in trees.rows_steppers().chain(trees.col_steppers()) {
+        line_view_distances(&trees, &mut scores, line.iter(), &mut stack);
+        line_view_distances(&trees, &mut scores, line.iter().rev(), &mut stack);
+    }
+
+    // the line terminators are never visited and keep their score of 1
+    let tree_scores = trees.rows_steppers().flat_map(|row| row.iter());
+    tree_scores
+        .map(|idx| scores.entries[idx])
+        .max()
+        .unwrap()
+        .to_string()
+}
+
+#[allow(unused)]
 fn part2_brute_force(input: &mut dyn BufRead) -> String {
-    let trees = Tiles::load(input, MAP_SIZE);
+    let trees = Tiles::load(input, 0);
     let mut views = Tiles::new(trees.width(), trees.height(), 0u32);
 
     for y in 1..trees.height() - 1 {
@@ -86,4 +124,38 @@ fn part2_brute_force(input: &mut dyn BufRead) -> String {
     views.entries.iter().max().unwrap().to_string()
 }
 
-pub const SOLVERS: &[Solver] = &[part1_count_visible_trees, part2_brute_force];
+pub const SOLVERS: &[Solver] = &[part1_count_visible_trees, part2_monotonic_stack];
+
+#[allow(unused)]
+mod test {
+    use super::*;
+    use crate::rng::XorShift32;
+
+    const EXAMPLE: &[u8] = b"30373\n25512\n65332\n33549\n35390\n";
+
+    #[test]
+    fn test_example() {
+        assert_eq!(part1_count_visible_trees(&mut &EXAMPLE[..]), "21");
+        assert_eq!(part2_monotonic_stack(&mut &EXAMPLE[..]), "8");
+        assert_eq!(part2_brute_force(&mut &EXAMPLE[..]), "8");
+    }
+
+    #[test]
+    fn monotonic_stack_agrees_with_brute_force() {
+        let mut rng = XorShift32::new(0x2545_f491);
+        let sizes = [(1, 1), (1, 6), (6, 1), (2, 2), (3, 3), (7, 4), (5, 12), (31, 29)];
+        for (width, height) in sizes {
+            let mut forest = Vec::new();
+            for _ in 0..height {
+                for _ in 0..width {
+                    forest.push(b'0' + (rng.next_u32() % 10) as u8);
+                }
+                forest.push(b'\n');
+            }
+            assert_eq!(
+                part2_monotonic_stack(&mut &forest[..]),
+                part2_brute_force(&mut &forest[..])
+            );
+        }
+    }
+}
@@ -1,81 +1,238 @@
 use std::{collections::HashSet, io::BufRead};
 
-use bstr::io::BufReadExt;
-
-use crate::{parse_num, Solver};
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-struct Knot(i16, i16);
-
-impl Knot {
-    pub fn move_towards(&mut self, other: &Knot) -> bool {
-        // assume that other is at most 2 steps in one direction and at most one in the other direction
-        let Knot(x1, y1) = self;
-        let Knot(x2, y2) = other;
-        if (*x1 - *x2).abs() >= 2 || (*y1 - *y2).abs() >= 2 {
-            *x1 += (*x2).cmp(x1) as i16;
-            *y1 += (*y2).cmp(y1) as i16;
-            true
-        } else {
-            false
+use bstr::{io::BufReadExt, ByteSlice};
+
+use crate::{parse_num, tiles::Point, Solver};
+
+/// One line of input: move the head `steps` times by `dir`, which may be
+/// diagonal, such as `UR 3`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Motion {
+    pub dir: Point,
+    pub steps: u64,
+}
+
+impl Motion {
+    pub fn parse(line: &[u8]) -> Self {
+        let (dirs, steps) = line.split_once_str(" ").expect("missing step count");
+        let mut dir = Point::new(0, 0);
+        for &d in dirs {
+            dir = dir
+                + match d {
+                    b'U' => Point::new(0, -1),
+                    b'D' => Point::new(0, 1),
+                    b'R' => Point::new(1, 0),
+                    b'L' => Point::new(-1, 0),
+                    _ => panic!("invalid direction"),
+                };
+        }
+        assert!(dir.x.abs() <= 1 && dir.y.abs() <= 1, "invalid direction");
+        Self {
+            dir,
+            steps: parse_num(steps),
         }
     }
+}
+
+/// Rope of knots starting on top of each other at the origin, with the cells
+/// each knot has visited.
+#[derive(Debug, Clone)]
+pub struct Rope {
+    knots: Vec<Point>,
+    visited: Vec<HashSet<Point>>,
+}
 
-    pub fn move_dir(&mut self, dir: u8) {
-        let Knot(x, y) = self;
-        match dir {
-            b'U' => *y -= 1,
-            b'D' => *y += 1,
-            b'R' => *x += 1,
-            b'L' => *x -= 1,
-            _ => panic!("invalid direction"),
+impl Rope {
+    pub fn new(knots: usize) -> Self {
+        assert!(knots > 0, "a rope needs a head");
+        let start = Point::new(0, 0);
+        Self {
+            knots: vec![start; knots],
+            visited: vec![HashSet::from([start]); knots],
         }
     }
-}
 
-fn part1_tail_positions(mut input: &mut dyn BufRead) -> String {
-    let mut positions = HashSet::with_capacity(8000);
-    let mut head = Knot(0, 0);
-    let mut tail = Knot(0, 0);
-    input
-        .for_byte_line(|line| {
-            let dir = line[0];
-            let num = parse_num(&line[2..]);
-            for _ in 0..num {
-                head.move_dir(dir);
-                tail.move_towards(&head);
-                positions.insert(tail);
+    /// Positions of the knots, head first.
+    pub fn knots(&self) -> &[Point] {
+        &self.knots
+    }
+
+    pub fn visited(&self, knot: usize) -> &HashSet<Point> {
+        &self.visited[knot]
+    }
+
+    pub fn tail_visited(&self) -> &HashSet<Point> {
+        self.visited.last().unwrap()
+    }
+
+    /// Move the head by `dir` and let the other knots follow.
+    pub fn step(&mut self, dir: Point) {
+        self.knots[0] = self.knots[0] + dir;
+        self.visited[0].insert(self.knots[0]);
+        for i in 1..self.knots.len() {
+            let (head, tail) = (self.knots[i - 1], self.knots[i]);
+            if (head.x - tail.x).abs() < 2 && (head.y - tail.y).abs() < 2 {
+                // still touching, so the rest of the rope stays put as well
+                break;
             }
-            Ok(true)
-        })
-        .unwrap();
+            let pull = Point::new((head.x - tail.x).signum(), (head.y - tail.y).signum());
+            self.knots[i] = tail + pull;
+            self.visited[i].insert(self.knots[i]);
+        }
+    }
 
-    positions.len().to_string()
-}
+    pub fn apply(&mut self, motion: Motion) {
+        for _ in 0..motion.steps {
+            self.step(motion.dir);
+        }
+    }
 
-fn part2_long_tail(mut input: &mut dyn BufRead) -> String {
-    const KNOTS: usize = 10;
-    let mut positions = HashSet::with_capacity(8000);
-    let mut knots = [Knot(0, 0); KNOTS];
-    input
-        .for_byte_line(|line| {
-            let dir = line[0];
-            let num = parse_num(&line[2..]);
-            for _ in 0..num {
-                knots[0].move_dir(dir);
-                for i in 1..KNOTS {
-                    let (head, tail) = knots.split_at_mut(i);
-                    if !tail[0].move_towards(&head[i - 1]) {
-                        break;
-                    }
-                }
-                positions.insert(knots[KNOTS - 1]);
+    /// Label of a knot in the puzzle's diagrams.
+    fn label(&self, knot: usize) -> char {
+        match knot {
+            0 => 'H',
+            _ if self.knots.len() == 2 => 'T',
+            _ => std::char::from_digit(knot as u32, 36).unwrap_or('#'),
+        }
+    }
+
+    fn render_with(&self, top_left: Point, size: Point, cell: impl Fn(Point) -> char) -> String {
+        let mut out = String::new();
+        for y in 0..size.y {
+            for x in 0..size.x {
+                out.push(cell(top_left + Point::new(x, y)));
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Draw the area of `size` cells from `top_left` like the puzzle does,
+    /// with the front-most knot on each cell and `s` for the start.
+    pub fn render(&self, top_left: Point, size: Point) -> String {
+        self.render_with(top_left, size, |p| {
+            match self.knots.iter().position(|&knot| knot == p) {
+                Some(knot) => self.label(knot),
+                None if p == Point::new(0, 0) => 's',
+                None => '.',
             }
-            Ok(true)
         })
-        .unwrap();
+    }
 
-    positions.len().to_string()
+    /// Draw the cells visited by `knot` with `#`, and `s` for the start.
+    pub fn render_visited(&self, knot: usize, top_left: Point, size: Point) -> String {
+        self.render_with(top_left, size, |p| {
+            if p == Point::new(0, 0) {
+                's'
+            } else if self.visited[knot].contains(&p) {
+                '#'
+            } else {
+                '.'
+            }
+        })
+    }
+}
+
+fn tail_positions(input: &mut dyn BufRead, knots: usize) -> usize {
+    let mut rope = Rope::new(knots);
+    for line in input.byte_lines() {
+        rope.apply(Motion::parse(&line.unwrap()));
+    }
+    rope.tail_visited().len()
+}
+
+fn part1_tail_positions(input: &mut dyn BufRead) -> String {
+    tail_positions(input, 2).to_string()
+}
+
+fn part2_long_tail(input: &mut dyn BufRead) -> String {
+    tail_positions(input, 10).to_string()
 }
 
 pub const SOLVERS: &[Solver] = &[part1_tail_positions, part2_long_tail];
+
+#[allow(unused)]
+mod test {
+    use super::*;
+
+    const SMALL: &str = "R 4\nU 4\nL 3\nD 1\nR 4\nD 1\nL 5\nR 2";
+    const LARGE: &str = "R 5\nU 8\nL 8\nD 3\nR 17\nD 10\nL 25\nU 20";
+
+    fn simulate(input: &str, knots: usize) -> Rope {
+        let mut rope = Rope::new(knots);
+        for line in input.lines() {
+            rope.apply(Motion::parse(line.as_bytes()));
+        }
+        rope
+    }
+
+    #[test]
+    fn test_small_example() {
+        let rope = simulate(SMALL, 2);
+        let top_left = Point::new(0, -4);
+        let size = Point::new(6, 5);
+        assert_eq!(
+            rope.render(top_left, size),
+            "......\n......\n.TH...\n......\ns.....\n"
+        );
+        assert_eq!(
+            rope.render_visited(1, top_left, size),
+            "..##..\n...##.\n.####.\n....#.\ns###..\n"
+        );
+        assert_eq!(rope.tail_visited().len(), 13);
+        assert_eq!(simulate(SMALL, 10).tail_visited().len(), 1);
+    }
+
+    #[test]
+    fn test_large_example() {
+        let top_left = Point::new(-11, -15);
+        let size = Point::new(26, 21);
+
+        let mut rope = Rope::new(10);
+        rope.apply(Motion::parse(b"R 5"));
+        let frame = rope.render(top_left, size);
+        assert_eq!(frame.lines().nth(15), Some("...........54321H........."));
+        assert_eq!(frame.matches(|c| c != '.' && c != '\n').count(), 6);
+
+        let rope = simulate(LARGE, 10);
+        assert_eq!(
+            rope.render_visited(9, top_left, size),
+            "\
+..........................
+..........................
+..........................
+..........................
+..........................
+..........................
+..........................
+..........................
+..........................
+#.........................
+#.............###.........
+#............#...#........
+.#..........#.....#.......
+..#..........#.....#......
+...#........#.......#.....
+....#......s.........#....
+.....#..............#.....
+......#............#......
+.......#..........#.......
+........#........#........
+.........########.........
+"
+        );
+        assert_eq!(rope.tail_visited().len(), 36);
+    }
+
+    #[test]
+    fn test_diagonal() {
+        let mut rope = Rope::new(3);
+        rope.apply(Motion::parse(b"UR 3"));
+        assert_eq!(
+            rope.knots(),
+            [Point::new(3, -3), Point::new(2, -2), Point::new(1, -1)]
+        );
+        assert_eq!(rope.visited(0).len(), 4);
+        assert_eq!(rope.visited(2).len(), 2);
+    }
+}
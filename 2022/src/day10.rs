@@ -2,46 +2,11 @@ use std::{io::BufRead, ops::Deref};
 
 use bstr::io::BufReadExt;
 
-use crate::{parse_num, Solver};
-
-pub const fn ltr(line1: u32, line2: u32, line3: u32, line4: u32, line5: u32, line6: u32) -> u32 {
-    line1 << (4 * 5)
-        | line2 << (4 * 4)
-        | line3 << (4 * 3)
-        | line4 << (4 * 2)
-        | line5 << (4 * 1)
-        | line6 << (4 * 0)
-}
-
-const LETTERS: [u32; 26] = [
-    // TODO formatting so that the letters are visible
-    ltr(0b0110, 0b1001, 0b1001, 0b1111, 0b1001, 0b1001),
-    ltr(0b1110, 0b1001, 0b1110, 0b1001, 0b1001, 0b1110),
-    ltr(0b0111, 0b1000, 0b1000, 0b1000, 0b1000, 0b0111),
-    ltr(0b1110, 0b1001, 0b1001, 0b1001, 0b1001, 0b1110),
-    ltr(0b1111, 0b1000, 0b1110, 0b1000, 0b1000, 0b1111),
-    ltr(0b1111, 0b1000, 0b1110, 0b1000, 0b1000, 0b1000),
-    ltr(0b0110, 0b1001, 0b1000, 0b1011, 0b1001, 0b0111),
-    ltr(0b1001, 0b1001, 0b1111, 0b1001, 0b1001, 0b1001),
-    ltr(0b111, 0b010, 0b010, 0b010, 0b010, 0b111),
-    ltr(0b0011, 0b0001, 0b0001, 0b0001, 0b1001, 0b0110),
-    ltr(0b1001, 0b1010, 0b1100, 0b1010, 0b1010, 0b1001),
-    ltr(0b1000, 0b1000, 0b1000, 0b1000, 0b1000, 0b1111),
-    ltr(0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001),
-    ltr(0b1001, 0b1101, 0b1011, 0b1001, 0b1001, 0b1001),
-    ltr(0b0110, 0b1001, 0b1001, 0b1001, 0b1001, 0b0110),
-    ltr(0b1110, 0b1001, 0b1001, 0b1110, 0b1000, 0b1000),
-    ltr(0b0110, 0b1001, 0b1001, 0b1001, 0b0110, 0b0001),
-    ltr(0b1110, 0b1001, 0b1001, 0b1110, 0b1010, 0b1001),
-    ltr(0b0111, 0b1000, 0b0110, 0b0001, 0b0001, 0b1110),
-    ltr(0b111, 0b010, 0b010, 0b010, 0b010, 0b010),
-    ltr(0b1001, 0b1001, 0b1001, 0b1001, 0b1001, 0b0110),
-    ltr(0b1001, 0b1001, 0b1001, 0b1001, 0b0110, 0b0110),
-    ltr(0b10001, 0b10001, 0b10101, 0b10101, 0b11011, 0b10001),
-    ltr(0b1001, 0b1001, 0b0110, 0b0110, 0b1001, 0b1001),
-    ltr(0b1001, 0b1001, 0b0110, 0b0010, 0b0010, 0b0010),
-    ltr(0b1111, 0b0001, 0b0010, 0b0100, 0b1000, 0b1111),
-];
+use crate::{
+    ocr::{self, Bitmap},
+    parse::parse,
+    Solver,
+};
 
 struct Computer<T>
 where
@@ -78,11 +43,7 @@ where
             b'a' => {
                 // addx
                 self.cycles_until_add = 2;
-                if instr[5] == b'-' {
-                    self.add_after = -(parse_num(&instr[6..]) as i32);
-                } else {
-                    self.add_after = parse_num(&instr[5..]) as i32;
-                }
+                self.add_after = parse(&instr[5..]);
             }
             _ => panic!("invalid instruction"),
         }
@@ -127,11 +88,7 @@ fn part1_interesting_values(input: &mut dyn BufRead) -> String {
                     b'a' => {
                         // addx
                         cycles_until_add = cycle + 2;
-                        if line[5] == b'-' {
-                            add_after = -(parse_num(&line[6..]) as i32);
-                        } else {
-                            add_after = parse_num(&line[5..]) as i32;
-                        }
+                        add_after = parse(&line[5..]);
                     }
                     _ => panic!("invalid instruction"),
                 }
@@ -162,35 +119,21 @@ fn part1_with_iter(input: &mut dyn BufRead) -> String {
 }
 
 fn part2_crt(input: &mut dyn BufRead) -> String {
+    const WIDTH: usize = 40;
+    const HEIGHT: usize = 6;
     let byte_lines = input.byte_lines().flatten();
     let mut computer = Computer::new(byte_lines);
     computer.step();
-    let mut cycle = 0;
-    let mut letters = [0u32; 8];
-    loop {
-        let pixel = cycle % 40;
-        let letter = (pixel / 5) as usize;
-        if pixel % 5 != 4 {
-            letters[letter] <<= 1;
-            if (pixel - computer.register).abs() <= 1 {
-                letters[letter] |= 1;
-            }
-        }
+    let mut screen = [false; WIDTH * HEIGHT];
+    for (cycle, lit) in screen.iter_mut().enumerate() {
+        let pixel = (cycle % WIDTH) as i32;
+        *lit = (pixel - computer.register).abs() <= 1;
         if !computer.step() {
             break;
         }
-        cycle += 1;
-    }
-    let mut result = String::with_capacity(8);
-    for letter in letters {
-        let idx = LETTERS.iter().position(|ltr| *ltr == letter);
-        result.push(if let Some(idx) = idx {
-            (idx as u8 + b'A') as char
-        } else {
-            '?'
-        });
     }
-    result
+    let image = Bitmap::from_fn(WIDTH, HEIGHT, |x, y| screen[y * WIDTH + x]);
+    ocr::recognize(&image, &ocr::SMALL).text_or_art()
 }
 
 pub const SOLVERS: &[Solver] = &[part1_with_iter, part2_crt];
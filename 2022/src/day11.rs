@@ -1,49 +1,140 @@
-use std::{collections::VecDeque, io::BufRead, ops::Deref};
+use std::{
+    collections::VecDeque,
+    io::{BufRead, Write},
+    ops::Deref,
+};
 
-use bstr::io::BufReadExt;
-
-use crate::{parse_num, parse_nums, Solver};
+use crate::{groups::Groups, parse::parse_checked, scan::Pattern, topk::TopK, Solver};
 
 const MAX_ITEMS: usize = 64;
 
-enum Operation {
-    Add(u64),
-    Multiply(u64),
-    Double,
-    Power,
-}
-
-impl Operation {
-    fn decode(slice: &[u8]) -> Self {
-        match slice[0] {
-            b'+' => {
-                if slice[2] == b'o' {
-                    // old + old
-                    Operation::Double
-                } else {
-                    // old + <number>
-                    Operation::Add(parse_num(&slice[2..]))
+/// Arithmetic over the old worry level, such as `old * (old + 3)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Old,
+    Const(u64),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    /// Parse `+`, `-` and `*` with the usual precedence, and parentheses.
+    /// Division is not supported, as it does not survive modular reduction.
+    pub fn parse(text: &[u8]) -> Self {
+        let mut parser = ExprParser { text, pos: 0 };
+        let expr = parser.sum();
+        if parser.peek().is_some() {
+            parser.fail("an operator");
+        }
+        expr
+    }
+
+    /// Evaluate for `old`, modulo `modulus` if given. Without a modulus, the
+    /// result must fit a `u64` and never go negative.
+    pub fn eval(&self, old: u64, modulus: Option<u64>) -> u64 {
+        let (left, right) = match self {
+            Expr::Old => return modulus.map_or(old, |m| old % m),
+            Expr::Const(n) => return modulus.map_or(*n, |m| n % m),
+            Expr::Add(left, right) | Expr::Sub(left, right) | Expr::Mul(left, right) => (
+                left.eval(old, modulus) as u128,
+                right.eval(old, modulus) as u128,
+            ),
+        };
+        // operands are below the modulus, so nothing overflows in u128
+        let value = match self {
+            Expr::Add(..) => left + right,
+            Expr::Mul(..) => left * right,
+            Expr::Sub(..) => match modulus {
+                Some(m) => left + m as u128 - right,
+                None => left.checked_sub(right).expect("negative worry level"),
+            },
+            _ => unreachable!(),
+        };
+        match modulus {
+            Some(m) => (value % m as u128) as u64,
+            None => value.try_into().expect("worry level overflow"),
+        }
+    }
+}
+
+struct ExprParser<'a> {
+    text: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ExprParser<'a> {
+    fn peek(&mut self) -> Option<u8> {
+        while self.text.get(self.pos) == Some(&b' ') {
+            self.pos += 1;
+        }
+        self.text.get(self.pos).copied()
+    }
+
+    fn fail(&self, expected: &str) -> ! {
+        panic!(
+            "expected {} at position {} in {:?}",
+            expected,
+            self.pos,
+            String::from_utf8_lossy(self.text)
+        )
+    }
+
+    fn sum(&mut self) -> Expr {
+        let mut expr = self.product();
+        loop {
+            match self.peek() {
+                Some(b'+') => {
+                    self.pos += 1;
+                    expr = Expr::Add(Box::new(expr), Box::new(self.product()));
                 }
-            }
-            b'*' => {
-                if slice[2] == b'o' {
-                    // old * old
-                    Operation::Power
-                } else {
-                    // old * <number>
-                    Operation::Multiply(parse_num(&slice[2..]))
+                Some(b'-') => {
+                    self.pos += 1;
+                    expr = Expr::Sub(Box::new(expr), Box::new(self.product()));
                 }
+                _ => return expr,
             }
-            _ => panic!("Invalid operation"),
         }
     }
 
-    fn apply(&self, old: u64) -> u64 {
-        match self {
-            Operation::Add(n) => old + n,
-            Operation::Multiply(n) => old * n,
-            Operation::Double => old * 2,
-            Operation::Power => old * old,
+    fn product(&mut self) -> Expr {
+        let mut expr = self.atom();
+        while self.peek() == Some(b'*') {
+            self.pos += 1;
+            expr = Expr::Mul(Box::new(expr), Box::new(self.atom()));
+        }
+        expr
+    }
+
+    fn atom(&mut self) -> Expr {
+        match self.peek() {
+            Some(b'(') => {
+                self.pos += 1;
+                let expr = self.sum();
+                if self.peek() != Some(b')') {
+                    self.fail("')'");
+                }
+                self.pos += 1;
+                expr
+            }
+            Some(b'0'..=b'9') => {
+                let start = self.pos;
+                while self.text.get(self.pos).is_some_and(u8::is_ascii_digit) {
+                    self.pos += 1;
+                }
+                match parse_checked(&self.text[start..self.pos]) {
+                    Ok(n) => Expr::Const(n),
+                    Err(_) => {
+                        self.pos = start;
+                        self.fail("a number that fits 64 bits")
+                    }
+                }
+            }
+            _ if self.text[self.pos..].starts_with(b"old") => {
+                self.pos += 3;
+                Expr::Old
+            }
+            _ => self.fail("a number, 'old' or '('"),
         }
     }
 }
@@ -55,35 +146,52 @@ struct ThrownItem {
 
 struct Monkey {
     items: VecDeque<u64>,
-    operation: Operation,
+    operation: Expr,
     test_div: u64,
     throw: [u32; 2],
     activity: u64,
 }
 
+/// Templates of the lines describing a monkey, in input order.
+struct MonkeyPatterns {
+    id: Pattern<'static>,
+    items: Pattern<'static>,
+    operation: Pattern<'static>,
+    test: Pattern<'static>,
+    if_true: Pattern<'static>,
+    if_false: Pattern<'static>,
+}
+
+impl MonkeyPatterns {
+    fn new() -> Self {
+        Self {
+            id: Pattern::new("Monkey {id}:"),
+            items: Pattern::new("  Starting items: {items}"),
+            operation: Pattern::new("  Operation: new = {expr}"),
+            test: Pattern::new("  Test: divisible by {divisor}"),
+            if_true: Pattern::new("    If true: throw to monkey {monkey}"),
+            if_false: Pattern::new("    If false: throw to monkey {monkey}"),
+        }
+    }
+}
+
 impl Monkey {
-    fn load(source: &mut impl Iterator<Item = impl Deref<Target = [u8]>>) -> Self {
-        // Monkey N:
-        source.next().unwrap();
-        //   Starting items: <number>, <number>, ...
-        let line = source.next().unwrap();
-        let mut numbers = [0; MAX_ITEMS];
-        let parsed = parse_nums(&line["  Starting items: ".len()..], &mut numbers);
-        //   Operation: new = old <op def>
-        let line = source.next().unwrap();
-        let operation = Operation::decode(&line["  Operation: new = old ".len()..]);
-        //   Test: divisible by <number>
-        let line = source.next().unwrap();
-        let test_div = parse_num(&line["  Test: divisible by ".len()..]);
-        //     If true: throw to monkey <number>
-        let line = source.next().unwrap();
-        let if_true = parse_num(&line["    If true: throw to monkey ".len()..]) as u32;
-        //     If false: throw to monkey <number>
-        let line = source.next().unwrap();
-        let if_false = parse_num(&line["    If false: throw to monkey ".len()..]) as u32;
+    fn load(
+        source: &mut impl Iterator<Item = impl Deref<Target = [u8]>>,
+        patterns: &MonkeyPatterns,
+    ) -> Self {
+        let mut next_line = || source.next().expect("incomplete monkey");
+        let (_,): (u32,) = patterns.id.scan(&next_line()).unwrap();
+        let (numbers,): (Vec<u64>,) = patterns.items.scan(&next_line()).unwrap();
+        let line = next_line();
+        let (op,) = patterns.operation.scan(&line).unwrap();
+        let operation = Expr::parse(op);
+        let (test_div,) = patterns.test.scan(&next_line()).unwrap();
+        let (if_true,) = patterns.if_true.scan(&next_line()).unwrap();
+        let (if_false,) = patterns.if_false.scan(&next_line()).unwrap();
 
         let mut items = VecDeque::with_capacity(MAX_ITEMS);
-        items.extend(numbers[..parsed].iter());
+        items.extend(numbers);
         Monkey {
             items,
             operation,
@@ -93,11 +201,10 @@ impl Monkey {
         }
     }
 
-    fn process_next(&mut self, div_worry: bool) -> Option<ThrownItem> {
+    fn process_next(&mut self, relief: u64, modulus: Option<u64>) -> Option<ThrownItem> {
         let item = self.items.pop_front()?;
         self.activity += 1;
-        let applied = self.operation.apply(item);
-        let new = if div_worry { applied / 3} else { applied };
+        let new = self.operation.eval(item, modulus) / relief;
         let monkey = self.throw[(new % self.test_div == 0) as usize];
         Some(ThrownItem { monkey, item: new })
     }
@@ -107,37 +214,153 @@ impl Monkey {
     }
 }
 
-fn dyn_monkey_business(input: &mut dyn BufRead, rounds: usize, div_worry: bool) -> String {
-    let mut lines = input.byte_lines().flatten();
-    let mut monkeys = Vec::with_capacity(20);
-    // assume at least one monkey
-    loop {
-        monkeys.push(Monkey::load(&mut lines));
-        if lines.next().is_none() {
-            break;
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// All monkeys playing keep away, with how active each was in every round.
+pub struct MonkeyGame {
+    monkeys: Vec<Monkey>,
+    /// Worry levels are divided by this after each inspection.
+    relief: u64,
+    /// Worry levels are kept modulo this, if that does not change any test.
+    modulus: Option<u64>,
+    /// Items inspected by each monkey, per round.
+    history: Vec<Vec<u64>>,
+}
+
+impl MonkeyGame {
+    pub fn load(input: &mut dyn BufRead, relief: u64) -> Self {
+        let patterns = MonkeyPatterns::new();
+        let monkeys = Groups::new(input)
+            .map(|group| Monkey::load(&mut group.into_iter(), &patterns))
+            .collect::<Vec<_>>();
+
+        // all tests only look at the worry level modulo their divisor, which
+        // survives reduction modulo the least common multiple; division for
+        // relief does not, so without relief is the only way to reduce
+        let modulus = (relief == 1).then(|| {
+            monkeys
+                .iter()
+                .fold(1, |lcm, m| lcm / gcd(lcm, m.test_div) * m.test_div)
+        });
+        Self {
+            monkeys,
+            relief,
+            modulus,
+            history: Vec::new(),
         }
     }
 
-    let modulo = monkeys.iter().map(|m| m.test_div).product::<u64>();
+    pub fn round(&mut self) {
+        let before = self.monkeys.iter().map(|m| m.activity).collect::<Vec<_>>();
+        for m in 0..self.monkeys.len() {
+            while let Some(ThrownItem { monkey, item }) =
+                self.monkeys[m].process_next(self.relief, self.modulus)
+            {
+                self.monkeys[monkey as usize].catch(item);
+            }
+        }
+        self.history.push(
+            self.monkeys
+                .iter()
+                .zip(before)
+                .map(|(m, before)| m.activity - before)
+                .collect(),
+        );
+    }
 
-    for _ in 0..rounds {
-        for m in 0..monkeys.len() {
-            while let Some(ThrownItem { monkey, item }) = monkeys[m].process_next(div_worry) {
-                monkeys[monkey as usize].catch(item % modulo);
+    /// Product of the two highest activities.
+    pub fn monkey_business(&self) -> u64 {
+        let top2: TopK<u64, 2> = self.monkeys.iter().map(|m| m.activity).collect();
+        top2.into_iter().product()
+    }
+
+    /// Items inspected by each monkey, per round.
+    pub fn history(&self) -> &[Vec<u64>] {
+        &self.history
+    }
+
+    /// Write the history as CSV, one line per round.
+    pub fn write_history(&self, out: &mut dyn Write) -> std::io::Result<()> {
+        write!(out, "round")?;
+        for m in 0..self.monkeys.len() {
+            write!(out, ",monkey{}", m)?;
+        }
+        writeln!(out)?;
+        for (round, activity) in self.history.iter().enumerate() {
+            write!(out, "{}", round + 1)?;
+            for count in activity {
+                write!(out, ",{}", count)?;
             }
+            writeln!(out)?;
         }
+        Ok(())
     }
+}
 
-    monkeys.sort_by_key(|m| m.activity);
-    (monkeys[monkeys.len() - 1].activity * monkeys[monkeys.len() - 2].activity).to_string()
+fn dyn_monkey_business(input: &mut dyn BufRead, rounds: usize, relief: u64) -> String {
+    let mut game = MonkeyGame::load(input, relief);
+    for _ in 0..rounds {
+        game.round();
+    }
+    game.monkey_business().to_string()
 }
 
 fn part1_monkey_business(input: &mut dyn BufRead) -> String {
-    dyn_monkey_business(input, 20, true)
+    dyn_monkey_business(input, 20, 3)
 }
 
 fn part2_monkey_business(input: &mut dyn BufRead) -> String {
-    dyn_monkey_business(input, 10_000, false)
+    dyn_monkey_business(input, 10_000, 1)
 }
 
 pub const SOLVERS: &[Solver] = &[part1_monkey_business, part2_monkey_business];
+
+#[allow(unused)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_expressions() {
+        let expr = Expr::parse(b"old * (old + 3) - 2*old");
+        assert_eq!(expr.eval(5, None), 30);
+        assert_eq!(expr.eval(5, Some(7)), 2);
+        assert_eq!(Expr::parse(b"old - 10").eval(3, Some(7)), 0);
+        assert_eq!(
+            Expr::parse(b"old * old").eval(u64::MAX - 1, Some(u64::MAX)),
+            1
+        );
+        assert_eq!(Expr::parse(b"old + old"), Expr::parse(b"(old) + (old)"));
+    }
+
+    #[test]
+    fn test_unreduced_start() {
+        // starting items above the modulus must be reduced before subtracting
+        assert_eq!(Expr::parse(b"0 - old").eval(10, Some(7)), 4);
+        let input = b"\
+Monkey 0:
+  Starting items: 10
+  Operation: new = 0 - old
+  Test: divisible by 7
+    If true: throw to monkey 1
+    If false: throw to monkey 1
+
+Monkey 1:
+  Starting items: 20
+  Operation: new = old + 0
+  Test: divisible by 7
+    If true: throw to monkey 0
+    If false: throw to monkey 0
+";
+        let mut game = MonkeyGame::load(&mut &input[..], 1);
+        for _ in 0..10 {
+            game.round();
+        }
+        assert_eq!(game.monkey_business(), 19 * 20);
+    }
+}
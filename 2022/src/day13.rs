@@ -1,4 +1,4 @@
-use std::{cmp::Ordering, io::BufRead, iter::Peekable};
+use std::{cmp::Ordering, fmt::Display, io::BufRead, iter::Peekable};
 
 use bstr::io::BufReadExt;
 
@@ -84,17 +84,6 @@ impl<I: Iterator<Item = u8>> Iterator for NumberTokenizer<I> {
     }
 }
 
-enum CmpState {
-    Same,
-    NumberDiffers(Ordering),
-    FakeListLevelLeft(u32),
-    FakeListLevelRight(u32),
-}
-
-fn is_digit(c: u8) -> bool {
-    digits!().contains(&c)
-}
-
 #[derive(Debug)]
 struct Number(Vec<u8>);
 
@@ -119,108 +108,6 @@ impl Number {
             };
         }
     }
-
-    fn compare_bad(&self, other: &Self) -> Ordering {
-        unimplemented!()
-        // let (mut left, mut right) = (self.0.iter().peekable(), other.0.iter().peekable());
-        // let mut state = CmpState::Same;
-        // loop {
-        //     let items = (left.peek(), right.peek());
-        //     let (Some(&&(mut a)), Some(&&(mut b))) = items else {
-        //         return match items {
-        //             (Some(_), None) => Ordering::Greater,
-        //             (None, Some(_)) => Ordering::Less,
-        //             (None, None) => Ordering::Equal,
-        //             _ => unreachable!(),
-        //         }
-        //     };
-        //     state = match state {
-        //         // fake list - left / right version, closing the lists
-        //         CmpState::FakeListLevelLeft(n) if !is_digit(b) => {
-        //             for _ in 0..n {
-        //                 if b != b']' {
-        //                     // left fake list is shorter
-        //                     return Ordering::Less;
-        //                 }
-        //                 right.next().expect("peek succeeded but next failed");
-        //                 b = **right.peek().expect("malformed right input - list not closed enough");
-        //             }
-        //             CmpState::Same
-        //         }
-        //         CmpState::FakeListLevelRight(n) if !is_digit(a) => {
-        //             for _ in 0..n {
-        //                 if a != b']' {
-        //                     // right fake list is shorter
-        //                     return Ordering::Greater;
-        //                 }
-        //                 left.next().expect("peek succeeded but next failed");
-        //                 a = **left.peek().expect("malformed left input - list not closed enough");
-        //             }
-        //             CmpState::Same
-        //         }
-        //         CmpState::NumberDiffers(ord) => {
-        //             if !is_digit(a) && !is_digit(b) {
-        //                 // numbers had the same length, previous ordering applies
-        //                 return ord;
-        //             } else if !is_digit(a) {
-        //                 // left number is shorter = smaller
-        //                 return Ordering::Less;
-        //             } else if !is_digit(b) {
-        //                 // right number is shorter = smaller
-        //                 return Ordering::Greater;
-        //             }
-        //             state
-        //         }
-        //         _ => match (a, b) {
-        //             (digits!(), b'[') => {
-        //                 let mut n = 0;
-        //                 while let Some(&&b'[') = right.peek() {
-        //                     n += 1;
-        //                     right.next().expect("peek succeeded but next failed");
-        //                 }
-        //                 state = CmpState::FakeListLevelLeft(n);
-        //                 continue;
-        //             }
-        //             (b'[', digits!()) => {
-        //                 let mut n = 0;
-        //                 while let Some(&&b'[') = left.peek() {
-        //                     n += 1;
-        //                     left.next().expect("peek succeeded but next failed");
-        //                 }
-        //                 state = CmpState::FakeListLevelRight(n);
-        //                 continue;
-        //             }
-        //             (digits!(), digits!()) if a != b => {
-        //                 // numbers differ
-        //                 CmpState::NumberDiffers(a.cmp(&b))
-        //             }
-        //             _ if a == b => {
-        //                 // same char, continue
-        //                 CmpState::Same
-        //             }
-        //             (digits!(), _) => {
-        //                 // shorter number wins
-        //                 return Ordering::Greater;
-        //             }
-        //             (_, digits!()) => {
-        //                 // shorter number wins
-        //                 return Ordering::Less;
-        //             }
-        //             (b',', b']') => {
-        //                 // shorter list wins, right is shorter
-        //                 return Ordering::Greater;
-        //             }
-        //             (b']', b',') => {
-        //                 // shorter list wins, left is shorter
-        //                 return Ordering::Less;
-        //             }
-        //             _ => panic!("chars are {} and {}", a as char, b as char),
-        //         }
-        //     };
-        //     left.next().expect("peek succeeded but next failed");
-        //     right.next().expect("peek succeeded but next failed");
-        // }
-    }
 }
 
 impl PartialEq for Number {
@@ -243,6 +130,149 @@ impl Ord for Number {
     }
 }
 
+/// Packet as a tree, for when the structure matters more than speed; the
+/// reference the streaming comparison of `Number` is tested against.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Packet {
+    Int(u32),
+    List(Vec<Packet>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketError {
+    UnexpectedByte {
+        pos: usize,
+        byte: u8,
+    },
+    UnexpectedEnd {
+        pos: usize,
+    },
+    /// Integer too large; position of its first digit.
+    Overflow {
+        pos: usize,
+    },
+}
+
+impl Display for PacketError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnexpectedByte { pos, byte } => {
+                write!(f, "unexpected {:?} at position {}", *byte as char, pos)
+            }
+            Self::UnexpectedEnd { pos } => write!(f, "packet ends early at position {}", pos),
+            Self::Overflow { pos } => write!(f, "integer too large at position {}", pos),
+        }
+    }
+}
+
+impl std::error::Error for PacketError {}
+
+impl Packet {
+    /// Parse a whole line. Works without recursion, so deep nesting is fine.
+    pub fn parse(line: &[u8]) -> Result<Self, PacketError> {
+        // lists that are still open, innermost last
+        let mut open: Vec<Vec<Packet>> = Vec::new();
+        let mut pos = 0;
+        loop {
+            let byte = *line.get(pos).ok_or(PacketError::UnexpectedEnd { pos })?;
+            let value = match byte {
+                b'[' => {
+                    pos += 1;
+                    if line.get(pos) == Some(&b']') {
+                        pos += 1;
+                        Packet::List(Vec::new())
+                    } else {
+                        open.push(Vec::new());
+                        continue;
+                    }
+                }
+                digits!() => {
+                    let start = pos;
+                    let mut num = 0u32;
+                    while let Some(&c @ digits!()) = line.get(pos) {
+                        num = num
+                            .checked_mul(10)
+                            .and_then(|n| n.checked_add((c - b'0') as u32))
+                            .ok_or(PacketError::Overflow { pos: start })?;
+                        pos += 1;
+                    }
+                    Packet::Int(num)
+                }
+                _ => return Err(PacketError::UnexpectedByte { pos, byte }),
+            };
+
+            // attach the value, closing as many lists as end here
+            let mut value = value;
+            loop {
+                let Some(list) = open.last_mut() else {
+                    return match line.get(pos) {
+                        None => Ok(value),
+                        Some(&byte) => Err(PacketError::UnexpectedByte { pos, byte }),
+                    };
+                };
+                list.push(value);
+                match line.get(pos) {
+                    Some(b',') => {
+                        pos += 1;
+                        break;
+                    }
+                    Some(b']') => {
+                        pos += 1;
+                        value = Packet::List(open.pop().unwrap());
+                    }
+                    Some(&byte) => return Err(PacketError::UnexpectedByte { pos, byte }),
+                    None => return Err(PacketError::UnexpectedEnd { pos }),
+                }
+            }
+        }
+    }
+}
+
+impl Ord for Packet {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Packet::Int(a), Packet::Int(b)) => a.cmp(b),
+            (Packet::List(a), Packet::List(b)) => a.cmp(b),
+            // an integer compares like a list containing only that integer
+            (Packet::Int(_), Packet::List(b)) => std::slice::from_ref(self).cmp(b.as_slice()),
+            (Packet::List(a), Packet::Int(_)) => a.as_slice().cmp(std::slice::from_ref(other)),
+        }
+    }
+}
+
+impl PartialOrd for Packet {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Display for Packet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Packet::Int(n) => write!(f, "{}", n),
+            Packet::List(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+/// Wrap a line for the streaming comparison, which assumes a valid packet,
+/// after checking it with the full parser.
+fn load_number(line: Vec<u8>) -> Number {
+    if let Err(err) = Packet::parse(&line) {
+        panic!("bad packet {}: {}", String::from_utf8_lossy(&line), err);
+    }
+    Number(line)
+}
+
 fn part1_compare_by_pairs(input: &mut dyn BufRead) -> String {
     let mut lines = input.byte_lines();
     let mut i = 1;
@@ -250,8 +280,8 @@ fn part1_compare_by_pairs(input: &mut dyn BufRead) -> String {
     loop {
         let a = lines.next().unwrap().unwrap();
         let b = lines.next().unwrap().unwrap();
-        let left = Number(a);
-        let right = Number(b);
+        let left = load_number(a);
+        let right = load_number(b);
         if left <= right {
             pairs_sum += i;
         }
@@ -267,7 +297,7 @@ fn part2_compare_all(input: &mut dyn BufRead) -> String {
     let mut numbers = input
         .byte_lines()
         .flatten()
-        .filter_map(|line| (!line.is_empty()).then(move || Number(line)))
+        .filter_map(|line| (!line.is_empty()).then(move || load_number(line)))
         .collect::<Vec<Number>>();
     let two = b"[[2]]";
     let six = b"[[6]]";
@@ -290,9 +320,10 @@ fn part2_compare_all(input: &mut dyn BufRead) -> String {
 
 pub const SOLVERS: &[Solver] = &[part1_compare_by_pairs, part2_compare_all];
 
+#[allow(unused)]
 mod test {
-    #[allow(unused_imports)]
     use super::*;
+    use crate::rng::XorShift32;
 
     #[test]
     fn basic_comparison() {
@@ -307,4 +338,74 @@ mod test {
         let right = b"[[7,4],[[[8,7],3],[0,8,9],6],[8,[3,[],5,[10,0],2],[1,2,[9,4],0]],[[],[],10,[[3],4,2]],[10,[8,7],4,[[3,3,5,6],[],[9,8,4,1],[0],10],[]]]";
         assert!(Number(left.to_vec()) > Number(right.to_vec()));
     }
+
+    #[test]
+    fn packet_round_trip() {
+        for line in [
+            &b"[1,[2,[3,[4,[5,6,7]]]],8,9]"[..],
+            b"[[[]]]",
+            b"[]",
+            b"[10,[],[0]]",
+            b"7",
+        ] {
+            let packet = Packet::parse(line).unwrap();
+            assert_eq!(packet.to_string().as_bytes(), line);
+        }
+    }
+
+    #[test]
+    fn packet_errors() {
+        use PacketError::*;
+        assert_eq!(Packet::parse(b"[1,2"), Err(UnexpectedEnd { pos: 4 }));
+        assert_eq!(
+            Packet::parse(b"[1,,2]"),
+            Err(UnexpectedByte { pos: 3, byte: b',' })
+        );
+        assert_eq!(
+            Packet::parse(b"[1]]"),
+            Err(UnexpectedByte { pos: 3, byte: b']' })
+        );
+        assert_eq!(
+            Packet::parse(b"[1 2]"),
+            Err(UnexpectedByte { pos: 2, byte: b' ' })
+        );
+        assert_eq!(Packet::parse(b"[5000000000]"), Err(Overflow { pos: 1 }));
+        assert_eq!(Packet::parse(b""), Err(UnexpectedEnd { pos: 0 }));
+    }
+
+    #[test]
+    #[should_panic(expected = "bad packet [1,,2]: unexpected ',' at position 3")]
+    fn solver_reports_bad_packet() {
+        part1_compare_by_pairs(&mut &b"[1]\n[1,,2]\n"[..]);
+    }
+
+    struct Rng(XorShift32);
+
+    impl Rng {
+        fn below(&mut self, n: u32) -> u32 {
+            self.0.next_u32() % n
+        }
+
+        fn packet(&mut self, depth: u32) -> Packet {
+            if depth == 0 || self.below(3) == 0 {
+                // few distinct values, so that comparisons often go deep
+                Packet::Int([0, 1, 2, 10][self.below(4) as usize])
+            } else {
+                let len = self.below(4) as usize;
+                Packet::List((0..len).map(|_| self.packet(depth - 1)).collect())
+            }
+        }
+    }
+
+    #[test]
+    fn streaming_comparison_agrees_with_tree() {
+        let mut rng = Rng(XorShift32::new(0x9e37_79b9));
+        for _ in 0..20_000 {
+            let left = Packet::List(vec![rng.packet(4), rng.packet(4)]);
+            let right = Packet::List(vec![rng.packet(4), rng.packet(4)]);
+            let streamed = Number(left.to_string().into_bytes())
+                .compare(&Number(right.to_string().into_bytes()));
+            assert_eq!(streamed, left.cmp(&right), "{} vs {}", left, right);
+        }
+    }
 }
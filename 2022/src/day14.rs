@@ -1,14 +1,16 @@
-use std::collections::{HashSet, VecDeque};
 use std::io::BufRead;
 
 use bstr::io::BufReadExt;
 use itertools::Itertools;
 
-use crate::tiles::Point;
+use crate::tiles::{Point, Tiles};
 use crate::{parse_nums, Solver};
 
-const EXPECTED_DEPTH: usize = 250;
-const EXPECTED_WIDTH: usize = 1000;
+const SOURCE: Point = Point::new(500, 0);
+
+const AIR: u8 = b'.';
+const ROCK: u8 = b'#';
+const SAND: u8 = b'o';
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 enum SandDir {
@@ -35,103 +37,265 @@ impl SandDir {
     }
 }
 
-fn add_pixels(set: &mut HashSet<Point>, rock_line: &[u8]) -> i16 {
+fn parse_path(rock_line: &[u8]) -> Vec<Point> {
     let mut numbers = [0; 64];
     let n = parse_nums(rock_line, &mut numbers);
-    let mut deepest_y = 0;
-
-    for (l, r) in numbers[..n]
+    numbers[..n]
         .iter()
         .copied()
         .tuples::<(u64, u64)>()
-        .map(|t| Point::from(t))
-        .tuple_windows()
-    {
-        if l.x == r.x {
-            let (miny, maxy) = if l.y < r.y { (l.y, r.y) } else { (r.y, l.y) };
-            for y in miny..=maxy {
-                set.insert(Point::from((l.x, y)));
-            }
-        } else {
-            let (minx, maxx) = if l.x < r.x { (l.x, r.x) } else { (r.x, l.x) };
-            for x in minx..=maxx {
-                set.insert(Point::from((x, l.y)));
-            }
+        .map(Point::from)
+        .collect()
+}
+
+/// Renders the cave every `every` settled grains while sand is poured.
+#[derive(Debug, Clone)]
+pub struct FrameRecorder {
+    every: u32,
+    area: Option<(Point, Point)>,
+    /// Number of settled grains and the picture at that point.
+    pub frames: Vec<(u32, String)>,
+}
+
+impl FrameRecorder {
+    pub fn new(every: u32) -> Self {
+        assert!(every > 0);
+        Self {
+            every,
+            area: None,
+            frames: Vec::new(),
         }
-        deepest_y = deepest_y.max(l.y.max(r.y));
     }
-    deepest_y
+
+    /// Only render `size` cells from `top_left` instead of the whole cave.
+    pub fn area(mut self, top_left: Point, size: Point) -> Self {
+        self.area = Some((top_left, size));
+        self
+    }
+
+    fn record(&mut self, cave: &Cave, grains: u32) {
+        let (top_left, size) = self.area.unwrap_or_else(|| cave.bounds());
+        self.frames.push((grains, cave.render(top_left, size)));
+    }
+}
+
+/// Vertical slice of the cave, just large enough for all rock and all sand
+/// that can settle.
+#[derive(Clone)]
+pub struct Cave {
+    tiles: Tiles<u8>,
+    /// Cave coordinates of the top left tile.
+    offset: Point,
 }
 
-fn part1_trace_sand(mut input: &mut dyn BufRead) -> String {
-    let mut pixels = HashSet::with_capacity(EXPECTED_DEPTH * EXPECTED_WIDTH / 2);
-    let mut deepest_y = 0;
-    input.for_byte_line(|line| {
-        deepest_y = deepest_y.max(add_pixels(&mut pixels, line));
-        Ok(true)
-    }).unwrap();
-
-    let mut sand_stack = Vec::with_capacity(deepest_y as usize);
-    let mut cur_pos = Point::from((500, 0));
-    let mut cur_dir = SandDir::Down;
-
-    let mut settled_grains = 0u32;
-
-    loop {
-        let next = cur_dir.fall(cur_pos);
-        if !pixels.contains(&next) {
-            if next.y > deepest_y {
-                break;
+impl Cave {
+    /// With `floor`, there is an endless floor two below the deepest rock;
+    /// without it, sand falling past the rock is lost in the abyss.
+    pub fn load(input: &mut dyn BufRead, floor: bool) -> Self {
+        let paths = input
+            .byte_lines()
+            .map(|line| parse_path(&line.unwrap()))
+            .collect::<Vec<_>>();
+        let (mut min, mut max) = (SOURCE, SOURCE);
+        for &p in paths.iter().flatten() {
+            min = min.min_bound(p);
+            max = max.max_bound(p);
+        }
+
+        let floor_y = max.y + 2;
+        if floor {
+            // sand piles up in a triangle of slope 1 below the source
+            min = min.min_bound(Point::new(SOURCE.x - floor_y, 0));
+            max = Point::new(max.x.max(SOURCE.x + floor_y), floor_y);
+        }
+        // one column of air on each side, for sand to fall past the rock
+        let offset = Point::new(min.x - 1, 0);
+        let size = max - offset + Point::new(2, 1);
+
+        let mut cave = Self {
+            tiles: Tiles::new(size.x as usize, size.y as usize, AIR),
+            offset,
+        };
+        for path in &paths {
+            for (l, r) in path.iter().tuple_windows() {
+                let (from, to) = (l.min_bound(*r), l.max_bound(*r));
+                for y in from.y..=to.y {
+                    for x in from.x..=to.x {
+                        cave.set(Point::new(x, y), ROCK);
+                    }
+                }
             }
-            sand_stack.push((cur_pos, cur_dir));
-            cur_pos = next;
-            cur_dir = SandDir::Down;
-            continue;
         }
-        // now `next` is trying to fall on an occupied pixel
-        if let Some(next_dir) = cur_dir.next() {
-            cur_dir = next_dir;
-            continue;
+        if floor {
+            for x in 0..size.x {
+                cave.set(offset + Point::new(x, floor_y), ROCK);
+            }
         }
-        // now there are no more options where to fall -> grain is settling
-        settled_grains += 1;
-        pixels.insert(cur_pos);
-        let (next_pos, next_dir) = sand_stack.pop().unwrap();
-        cur_pos = next_pos;
-        cur_dir = next_dir;
+        cave
     }
 
-    settled_grains.to_string()
-}
+    /// Top left corner and size of the simulated area, in cave coordinates.
+    pub fn bounds(&self) -> (Point, Point) {
+        (self.offset, self.tiles.size())
+    }
 
-fn part2_bfs_fill(mut input: &mut dyn BufRead) -> String {
-    let mut pixels = HashSet::with_capacity(EXPECTED_DEPTH * EXPECTED_WIDTH / 2);
-    let mut deepest_y = 0;
-    input.for_byte_line(|line| {
-        deepest_y = deepest_y.max(add_pixels(&mut pixels, line));
-        Ok(true)
-    }).unwrap();
-
-    let limit = deepest_y + 1;
-    let mut queue = VecDeque::with_capacity(deepest_y as usize * 2);
-    queue.push_back(Point::new(500, 0));
-    let mut settled_grains = 1u32;
-
-    while let Some(cur_pos) = queue.pop_front() {
-        if cur_pos.y >= limit {
-            continue;
+    /// Tile at `p`, or `None` in the abyss.
+    fn get(&self, p: Point) -> Option<u8> {
+        self.tiles.get(p - self.offset).copied()
+    }
+
+    fn set(&mut self, p: Point, tile: u8) {
+        let (x, y) = (p - self.offset).into();
+        self.tiles[(x, y)] = tile;
+    }
+
+    /// Let grains fall from the source until they either fall into the abyss
+    /// or block the source, and return how many settled.
+    ///
+    /// Every grain follows the path of the previous one up to where that one
+    /// settled, so the path is kept as a stack of positions and the direction
+    /// tried last at each.
+    pub fn pour(&mut self, mut recorder: Option<&mut FrameRecorder>) -> u32 {
+        let mut trace = Vec::with_capacity(self.tiles.height());
+        let mut cur_pos = SOURCE;
+        let mut cur_dir = SandDir::Down;
+        let mut settled_grains = 0u32;
+
+        loop {
+            let next = cur_dir.fall(cur_pos);
+            match self.get(next) {
+                None => break, // into the abyss
+                Some(AIR) => {
+                    trace.push((cur_pos, cur_dir));
+                    cur_pos = next;
+                    cur_dir = SandDir::Down;
+                    continue;
+                }
+                Some(_) => {}
+            }
+            // now `next` is trying to fall on an occupied tile
+            if let Some(next_dir) = cur_dir.next() {
+                cur_dir = next_dir;
+                continue;
+            }
+            // now there are no more options where to fall -> grain is settling
+            settled_grains += 1;
+            self.set(cur_pos, SAND);
+            if let Some(recorder) = recorder.as_deref_mut() {
+                if settled_grains.is_multiple_of(recorder.every) {
+                    recorder.record(self, settled_grains);
+                }
+            }
+            match trace.pop() {
+                Some((prev_pos, prev_dir)) => (cur_pos, cur_dir) = (prev_pos, prev_dir),
+                None => break, // the source is blocked
+            }
         }
-        for dir in [SandDir::Down, SandDir::Left, SandDir::Right] {
-            let next = dir.fall(cur_pos);
-            if !pixels.contains(&next) {
-                queue.push_back(next);
-                pixels.insert(next);
-                settled_grains += 1;
+        settled_grains
+    }
+
+    /// Draw `size` tiles from `top_left` like the puzzle does, with `+` for
+    /// the source.
+    pub fn render(&self, top_left: Point, size: Point) -> String {
+        let mut out = String::with_capacity((size.x as usize + 1) * size.y as usize);
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let p = top_left + Point::new(x, y);
+                out.push(match self.get(p) {
+                    Some(AIR) | None if p == SOURCE => '+',
+                    Some(tile) => tile as char,
+                    None => AIR as char,
+                });
             }
+            out.push('\n');
         }
+        out
     }
-    settled_grains.to_string()
 }
 
+fn part1_trace_sand(input: &mut dyn BufRead) -> String {
+    Cave::load(input, false).pour(None).to_string()
+}
+
+fn part2_fill_to_floor(input: &mut dyn BufRead) -> String {
+    Cave::load(input, true).pour(None).to_string()
+}
+
+pub const SOLVERS: &[Solver] = &[part1_trace_sand, part2_fill_to_floor];
+
+#[allow(unused)]
+mod test {
+    use super::*;
 
-pub const SOLVERS: &[Solver] = &[part1_trace_sand, part2_bfs_fill];
+    const EXAMPLE: &[u8] = b"498,4 -> 498,6 -> 496,6\n503,4 -> 502,4 -> 502,9 -> 494,9\n";
+
+    #[test]
+    fn test_example_frames() {
+        let mut recorder = FrameRecorder::new(1).area(Point::new(494, 0), Point::new(10, 10));
+        let mut cave = Cave::load(&mut &EXAMPLE[..], false);
+        assert_eq!(cave.pour(Some(&mut recorder)), 24);
+        assert_eq!(recorder.frames.len(), 24);
+        assert_eq!(
+            recorder.frames[0],
+            (
+                1,
+                "\
+......+...
+..........
+..........
+..........
+....#...##
+....#...#.
+..###...#.
+........#.
+......o.#.
+#########.
+"
+                .to_string()
+            )
+        );
+        assert_eq!(
+            recorder.frames[21].1,
+            "\
+......+...
+..........
+......o...
+.....ooo..
+....#ooo##
+....#ooo#.
+..###ooo#.
+....oooo#.
+...ooooo#.
+#########.
+"
+        );
+        assert_eq!(
+            recorder.frames[23].1,
+            "\
+......+...
+..........
+......o...
+.....ooo..
+....#ooo##
+...o#ooo#.
+..###ooo#.
+....oooo#.
+.o.ooooo#.
+#########.
+"
+        );
+    }
+
+    #[test]
+    fn test_floor() {
+        let mut cave = Cave::load(&mut &EXAMPLE[..], true);
+        let (top_left, size) = cave.bounds();
+        assert_eq!(size.y, 12);
+        let mut recorder = FrameRecorder::new(50);
+        assert_eq!(cave.pour(Some(&mut recorder)), 93);
+        assert_eq!(recorder.frames.len(), 1);
+        let last = cave.render(top_left, size);
+        assert!(last.lines().next().unwrap().contains(".o."));
+        assert!(last.lines().last().unwrap().bytes().all(|b| b == ROCK));
+    }
+}
@@ -2,82 +2,76 @@ use std::{cmp::Reverse, io::BufRead};
 
 use bstr::io::BufReadExt;
 
-use crate::{bitset::BitSet, parse_num, tiles::Tiles, Solver};
+use crate::{
+    bitset::WideBitSet,
+    branch_bound::{BranchAndBound, SearchState},
+    graph::{Graph, Interner, UNREACHABLE},
+    scan::Pattern,
+    tiles::Tiles,
+    Solver,
+};
 
-const MAX_NODES: usize = 60;
+const MAX_NODES: usize = 128;
 
-struct Graph {
+type ValveSet = WideBitSet<{ MAX_NODES / 64 }>;
+
+/// Name, flow rate, two ignored words, and names of the neighbors.
+type ValveLine<'a> = ([u8; 2], u32, &'a [u8], &'a [u8], Vec<[u8; 2]>);
+
+/// The valves worth opening plus the start, with the distances between them.
+struct Valves {
     start_idx: usize,
     rates: Vec<u32>,
-    neighbors: Tiles<u32>,
+    distances: Tiles<u32>,
     have_flow: Vec<usize>,
 }
 
-impl Graph {
+impl Valves {
     fn load(lines: impl Iterator<Item = Vec<u8>>) -> Self {
-        let mut names = Vec::with_capacity(MAX_NODES);
-        let mut rates = Vec::with_capacity(MAX_NODES);
-        let mut edges = Vec::with_capacity(MAX_NODES);
-        let mut start_idx = 0;
+        let mut names = Interner::new();
+        let mut tunnels = Graph::new(0);
+        let mut all_rates = Vec::with_capacity(MAX_NODES);
+        let pattern = Pattern::new("Valve {name} has flow rate={rate}; {_} to {_} {tunnels}");
         for line in lines {
-            let pieces = line.split(|c| *c == b' ');
-            let mut pieces = pieces.skip(1); // Valve
-            let name: [u8; 2] = pieces.next().unwrap().try_into().unwrap();
-            names.push(name);
-            if &name == b"AA" {
-                start_idx = names.len() - 1;
+            let (name, rate, _, _, neighbors): ValveLine = pattern.scan(&line).unwrap();
+            let idx = names.intern(name);
+            let neighbors = neighbors
+                .into_iter()
+                .map(|neighbor| names.intern(neighbor))
+                .collect::<Vec<_>>();
+            while tunnels.len() < names.len() {
+                tunnels.add_node();
             }
-
-            let mut pieces = pieces.skip(2); // has flow
-            let rate = pieces.next().unwrap();
-            rates.push(parse_num(&rate[b"rate=".len()..]) as u32);
-
-            let pieces = pieces.skip(4); // tunnel(s) lead(s) to valve(s)
-            edges.push(
-                pieces
-                    .map(|p| TryInto::<[u8; 2]>::try_into(&p[..2]).unwrap())
-                    .collect::<Vec<_>>(),
-            );
-        }
-        let mut tiles = Tiles::new(names.len(), names.len(), u32::MAX);
-        for (idx, edge) in edges.into_iter().enumerate() {
-            for dest in edge {
-                let dest = names.iter().position(|name| name == &dest).unwrap();
-                tiles[(idx, dest)] = 1;
-                tiles[(dest, idx)] = 1;
+            // every tunnel is listed at both ends
+            for neighbor in neighbors {
+                tunnels.add_edge(idx, neighbor, 1);
             }
+            all_rates.resize(names.len(), 0);
+            all_rates[idx] = rate;
         }
 
+        // only the start and valves with flow matter
+        let start = names.get(b"AA").unwrap();
+        let mut keep = vec![start];
+        keep.extend((0..names.len()).filter(|&idx| idx != start && all_rates[idx] > 0));
+        assert!(keep.len() <= MAX_NODES, "too many valves");
+        let rates = keep.iter().map(|&idx| all_rates[idx]).collect::<Vec<_>>();
+        let distances = tunnels.contract(&keep).adjacency_matrix();
+
         let mut have_flow = (0..rates.len())
             .filter(|&idx| rates[idx] > 0)
             .collect::<Vec<usize>>();
         have_flow.sort_by_key(|&idx| Reverse(rates[idx]));
 
-        let mut res = Self {
-            start_idx,
+        Self {
+            start_idx: 0,
             rates,
+            distances,
             have_flow,
-            neighbors: tiles,
-        };
-        res.floyd_warshall();
-        res
-    }
-
-    fn floyd_warshall(&mut self) {
-        let tiles = &mut self.neighbors;
-        for x in 0..tiles.width() {
-            tiles[(x, x)] = 0;
-        }
-        for k in 0..tiles.width() {
-            for i in 0..tiles.width() {
-                for j in 0..tiles.width() {
-                    tiles[(i, j)] = tiles[(i, j)].min(tiles[(i, k)].saturating_add(tiles[(k, j)]));
-                }
-            }
         }
     }
 
-    fn flow_upper_bound_for(&self, closed: BitSet<u64>, remaining: u32) -> u32 {
+    fn flow_upper_bound_for(&self, closed: ValveSet, remaining: u32) -> u32 {
         let mut res = 0;
         for &idx in &self.have_flow {
             if closed.contains(idx) {
@@ -88,48 +82,81 @@ impl Graph {
     }
 
     fn best_path(&self, limit: u32) -> u32 {
-        let closed = BitSet::new_full_up_to(self.rates.len() - 1);
-        self.best_path_rec(self.start_idx, closed, limit, 0, 0)
+        let root = PathState {
+            position: self.start_idx,
+            closed: ValveSet::new_full_up_to(self.rates.len() - 1),
+            remaining: limit,
+            flow: 0,
+        };
+        BranchAndBound::new(self)
+            .parallel_depth(2)
+            .search(root)
+            .best_score
     }
+}
 
-    fn best_path_rec(
-        &self,
-        start: usize,
-        closed: BitSet<u64>,
-        remaining: u32,
-        current_flow: u32,
-        best_flow: u32,
-    ) -> u32 {
-        let mut best_flow = best_flow.max(current_flow);
-        if remaining == 0 {
-            return current_flow;
-        }
-        if current_flow + self.flow_upper_bound_for(closed, remaining) < best_flow {
-            return 0; // no point in continuing as we can't improve best flow
-        }
-        for next in self.have_flow.iter().copied().filter(|&idx| closed.contains(idx)) {
-            let time = self.neighbors[(start, next)] + 1;
-            if remaining <= time {
+/// Standing at `position` right after opening a valve, with `flow` being the
+/// total released by all valves opened so far until the time runs out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct PathState {
+    position: usize,
+    closed: ValveSet,
+    remaining: u32,
+    flow: u32,
+}
+
+impl SearchState for PathState {
+    type Problem = Valves;
+
+    fn score(&self, _valves: &Valves) -> u32 {
+        self.flow
+    }
+
+    fn bound(&self, valves: &Valves) -> u32 {
+        self.flow + valves.flow_upper_bound_for(self.closed, self.remaining)
+    }
+
+    fn successors(&self, valves: &Valves, out: &mut Vec<Self>) {
+        for next in valves.have_flow.iter().copied() {
+            if !self.closed.contains(next) {
+                continue;
+            }
+            let distance = valves.distances[(self.position, next)];
+            if distance == UNREACHABLE || self.remaining <= distance + 1 {
                 continue;
             }
-            let new_closed = closed.with_removed(next);
-            let new_remaining = remaining - time;
-            let new_flow = current_flow + self.rates[next] * new_remaining;
-            best_flow = best_flow.max(self.best_path_rec(
-                next,
-                new_closed,
-                new_remaining,
-                new_flow,
-                best_flow,
-            ));
+            let time = distance + 1;
+            let remaining = self.remaining - time;
+            out.push(Self {
+                position: next,
+                closed: self.closed.with_removed(next),
+                remaining,
+                flow: self.flow + valves.rates[next] * remaining,
+            });
         }
-        best_flow
     }
 }
 
 fn part1_best_flow_in_30_minutes(input: &mut dyn BufRead) -> String {
-    let graph = Graph::load(input.byte_lines().flatten());
-    graph.best_path(30).to_string()
+    let valves = Valves::load(input.byte_lines().flatten());
+    valves.best_path(30).to_string()
 }
 
 pub const SOLVERS: &[Solver] = &[part1_best_flow_in_30_minutes];
+
+#[allow(unused)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_unreachable_valve() {
+        // CC has the most flow but is not connected to the start
+        let input = b"\
+Valve AA has flow rate=0; tunnel leads to valve BB
+Valve BB has flow rate=2; tunnel leads to valve AA
+Valve CC has flow rate=50; tunnel leads to valve DD
+Valve DD has flow rate=0; tunnel leads to valve CC
+";
+        assert_eq!(part1_best_flow_in_30_minutes(&mut &input[..]), "56");
+    }
+}
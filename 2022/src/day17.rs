@@ -1,6 +1,6 @@
 use std::io::BufRead;
 
-use crate::Solver;
+use crate::{cycle::find_cycle, Solver};
 
 #[rustfmt::skip]
 mod shapes {
@@ -194,44 +194,92 @@ fn print_line(rocks: u8, falling: u8) {
     println!("|");
 }
 
-fn part1_tower_height(input: &mut dyn BufRead) -> String {
-    const ROCK_LIMIT: usize = 2022;
-
-    let mut chamber = Vec::with_capacity(5000);
-    let mut shape = Shape::new(&SHAPES[0], chamber.len());
-    let mut shape_counter = 0;
+/// Chamber with all settled rocks, and where we are in the jet pattern.
+struct Tower {
+    chamber: Vec<u8>,
+    directions: Vec<u8>,
+    next_direction: usize,
+    rocks: usize,
+}
 
-    let mut directions = Vec::with_capacity(15000);
-    input.read_until(b'\n', &mut directions).unwrap();
-    assert!(matches!(directions.pop(), Some(b'\n')));
+impl Tower {
+    /// Rows from the top used to recognize a repeating tower.
+    const FINGERPRINT_ROWS: usize = 32;
 
-    for dir in directions.iter().cycle() {
-        match dir {
-            b'>' => shape.try_shift(-1, &chamber),
-            b'<' => shape.try_shift(1, &chamber),
-            _ => unreachable!(),
+    fn load(input: &mut dyn BufRead) -> Self {
+        let mut directions = Vec::with_capacity(15000);
+        input.read_until(b'\n', &mut directions).unwrap();
+        assert!(matches!(directions.pop(), Some(b'\n')));
+        Self {
+            chamber: Vec::with_capacity(5000),
+            directions,
+            next_direction: 0,
+            rocks: 0,
         }
-        .unwrap_or_default();
-        // println!("{}", *dir as char);
-        // shape.print(&chamber);
-        // println!();
-        match shape.fall_or_settle(&mut chamber) {
-            FallOrSettle::Fall => {}
-            FallOrSettle::Settle => {
-                shape_counter += 1;
-                if shape_counter == ROCK_LIMIT {
-                    break;
-                }
-                shape = Shape::new(&SHAPES[shape_counter % SHAPES.len()], chamber.len());
+    }
+
+    fn height(&self) -> usize {
+        self.chamber.len()
+    }
+
+    fn drop_rock(&mut self) {
+        let mut shape = Shape::new(&SHAPES[self.rocks % SHAPES.len()], self.chamber.len());
+        loop {
+            let dir = self.directions[self.next_direction];
+            self.next_direction = (self.next_direction + 1) % self.directions.len();
+            match dir {
+                b'>' => shape.try_shift(-1, &self.chamber),
+                b'<' => shape.try_shift(1, &self.chamber),
+                _ => unreachable!(),
+            }
+            .unwrap_or_default();
+            // shape.print(&self.chamber);
+            // println!();
+            if shape.fall_or_settle(&mut self.chamber) == FallOrSettle::Settle {
+                break;
             }
         }
-        // shape.print(&chamber);
-        // println!();
+        self.rocks += 1;
     }
-    chamber.len().to_string()
+
+    /// Next shape, jet position, and the top of the tower. Rocks rarely fall
+    /// deeper than a few rows, so this is enough to predict the future.
+    fn fingerprint(&self) -> (usize, usize, [u8; Self::FINGERPRINT_ROWS]) {
+        let mut top = [0; Self::FINGERPRINT_ROWS];
+        for (row, rocks) in top.iter_mut().zip(self.chamber.iter().rev()) {
+            *row = *rocks;
+        }
+        (self.rocks % SHAPES.len(), self.next_direction, top)
+    }
+}
+
+fn part1_tower_height(input: &mut dyn BufRead) -> String {
+    const ROCK_LIMIT: usize = 2022;
+
+    let mut tower = Tower::load(input);
+    for _ in 0..ROCK_LIMIT {
+        tower.drop_rock();
+    }
+    tower.height().to_string()
+}
+
+fn part2_tower_height_extrapolated(input: &mut dyn BufRead) -> String {
+    const ROCK_LIMIT: usize = 1_000_000_000_000;
+
+    let mut tower = Tower::load(input);
+    let max_steps = 10 * SHAPES.len() * tower.directions.len();
+    let history = find_cycle(
+        &mut tower,
+        Tower::drop_rock,
+        Tower::fingerprint,
+        |tower| tower.height() as i64,
+        max_steps,
+    )
+    .expect("tower does not repeat");
+    history.value_after(ROCK_LIMIT).to_string()
 }
 
-pub const SOLVERS: &[Solver] = &[part1_tower_height];
+pub const SOLVERS: &[Solver] = &[part1_tower_height, part2_tower_height_extrapolated];
 
 #[allow(unused)]
 mod tests {
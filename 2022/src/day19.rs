@@ -1,54 +1,67 @@
-use core::time;
 use std::{
-    collections::HashSet,
+    fmt::Write,
     io::BufRead,
-    ops::{Add, Deref, Mul, Sub},
+    ops::{Add, Deref, Index, Mul, Sub},
 };
 
-use bstr::io::BufReadExt;
+use bstr::{io::BufReadExt, ByteSlice};
 use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
 
-use crate::{parse_nums, Solver};
+use crate::{
+    branch_bound::{BranchAndBound, SearchState},
+    graph::Interner,
+    scan::Pattern,
+    Solver,
+};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-struct Resources {
-    ore: u32,
-    clay: u32,
-    obsidian: u32,
-}
+/// Most resource kinds a blueprint may use. Each kind is mined by one kind of
+/// robot; kinds are numbered in the order their robots are listed.
+const MAX_KINDS: usize = 8;
+
+/// Longest simulation for which build schedules can be recorded.
+const MAX_MINUTES: u32 = 32;
+
+/// The kind whose amount at the end is the score.
+const SCORE_KIND: &[u8] = b"geode";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Resources([u32; MAX_KINDS]);
 
 impl Resources {
-    pub const fn new() -> Self {
-        Self {
-            ore: 0,
-            clay: 0,
-            obsidian: 0,
-        }
+    pub fn single(kind: usize, amount: u32) -> Self {
+        let mut res = Self::default();
+        res.0[kind] = amount;
+        res
     }
 
-    pub const fn add_ore(&self, ore: i32) -> Self {
-        Self {
-            ore: (self.ore as i32 + ore) as u32,
-            ..*self
-        }
+    pub fn can_buy(&self, other: &Self) -> bool {
+        self.0
+            .iter()
+            .zip(other.0.iter())
+            .all(|(have, cost)| have >= cost)
     }
 
-    pub const fn add_clay(&self, clay: i32) -> Self {
-        Self {
-            clay: (self.clay as i32 + clay) as u32,
-            ..*self
+    /// List the non-zero amounts, such as `3 ore, 1 clay`.
+    pub fn display(&self, names: &[String]) -> String {
+        let parts = names
+            .iter()
+            .zip(self.0.iter())
+            .filter(|(_, &amount)| amount > 0)
+            .map(|(name, amount)| format!("{} {}", amount, name))
+            .collect::<Vec<_>>();
+        if parts.is_empty() {
+            "nothing".to_string()
+        } else {
+            parts.join(", ")
         }
     }
+}
 
-    pub const fn add_obsidian(&self, obsidian: i32) -> Self {
-        Self {
-            obsidian: (self.obsidian as i32 + obsidian) as u32,
-            ..*self
-        }
-    }
+impl Index<usize> for Resources {
+    type Output = u32;
 
-    pub const fn can_buy(&self, other: &Self) -> bool {
-        self.ore >= other.ore && self.clay >= other.clay && self.obsidian >= other.obsidian
+    fn index(&self, kind: usize) -> &u32 {
+        &self.0[kind]
     }
 }
 
@@ -56,11 +69,7 @@ impl Add<Resources> for Resources {
     type Output = Self;
 
     fn add(self, rhs: Self) -> Self::Output {
-        Self {
-            ore: self.ore + rhs.ore,
-            clay: self.clay + rhs.clay,
-            obsidian: self.obsidian + rhs.obsidian,
-        }
+        Self(std::array::from_fn(|i| self.0[i] + rhs.0[i]))
     }
 }
 
@@ -68,11 +77,7 @@ impl Sub<Resources> for Resources {
     type Output = Self;
 
     fn sub(self, rhs: Self) -> Self::Output {
-        Self {
-            ore: self.ore - rhs.ore,
-            clay: self.clay - rhs.clay,
-            obsidian: self.obsidian - rhs.obsidian,
-        }
+        Self(std::array::from_fn(|i| self.0[i] - rhs.0[i]))
     }
 }
 
@@ -80,43 +85,87 @@ impl Mul<u32> for Resources {
     type Output = Self;
 
     fn mul(self, rhs: u32) -> Self::Output {
-        Self {
-            ore: self.ore * rhs,
-            clay: self.clay * rhs,
-            obsidian: self.obsidian * rhs,
-        }
+        Self(self.0.map(|n| n * rhs))
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-struct Blueprint {
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Blueprint {
     id: u32,
-    ore_cost: Resources,
-    clay_cost: Resources,
-    obsidian_cost: Resources,
-    geodes_cost: Resources,
-    max_ore_required: u32,
+    /// Name of each resource kind.
+    names: Vec<String>,
+    /// Cost of the robot mining each kind.
+    costs: Vec<Resources>,
+    /// More robots of a kind than this are useless, as no robot costs more.
+    max_useful: Resources,
+    score_kind: usize,
 }
 
 impl Blueprint {
     fn load(line: impl Deref<Target = [u8]>) -> Self {
-        let mut numbers = [0u64; 1 + 1 + 1 + 2 + 2];
-        assert_eq!(parse_nums(&line, &mut numbers), numbers.len());
-        let ore_cost = Resources::new().add_ore(numbers[1] as i32);
-        let clay_cost = Resources::new().add_ore(numbers[2] as i32);
-        let obsidian_cost = Resources::new()
-            .add_ore(numbers[3] as i32)
-            .add_clay(numbers[4] as i32);
-        let geodes_cost = Resources::new()
-            .add_ore(numbers[5] as i32)
-            .add_obsidian(numbers[6] as i32);
+        let (id, robots): (u32, &[u8]) = Pattern::new("Blueprint {id}: {robots}.")
+            .scan(&line)
+            .unwrap();
+        let robot_pattern = Pattern::new("Each {kind} robot costs {costs}");
+        let cost_pattern = Pattern::new("{amount} {kind}");
+
+        let mut kinds = Interner::new();
+        let mut robot_costs = Vec::new();
+        for robot in robots.split_str(". ") {
+            let (kind, costs): (&[u8], &[u8]) = robot_pattern.scan(robot).unwrap();
+            let kind = kinds.intern(kind);
+            let costs = costs
+                .split_str(" and ")
+                .map(|cost| {
+                    let (amount, cost_kind): (u32, &[u8]) = cost_pattern.scan(cost).unwrap();
+                    (kinds.intern(cost_kind), amount)
+                })
+                .collect::<Vec<_>>();
+            robot_costs.push((kind, costs));
+        }
+        assert!(kinds.len() <= MAX_KINDS, "too many resource kinds");
+        assert_eq!(robot_costs.len(), kinds.len(), "resource without robot");
+
+        let mut costs = vec![Resources::default(); kinds.len()];
+        for (kind, cost) in robot_costs {
+            for (cost_kind, amount) in cost {
+                costs[kind].0[cost_kind] += amount;
+            }
+        }
+        let max_useful = Resources(std::array::from_fn(|kind| {
+            costs.iter().map(|cost| cost[kind]).max().unwrap_or(0)
+        }));
+        let names = (0..kinds.len())
+            .map(|kind| String::from_utf8_lossy(kinds.name(kind)).into_owned())
+            .collect();
         Self {
-            id: numbers[0] as u32,
-            ore_cost,
-            clay_cost,
-            obsidian_cost,
-            geodes_cost,
-            max_ore_required: clay_cost.ore.max(obsidian_cost.ore).max(geodes_cost.ore),
+            id,
+            names,
+            costs,
+            max_useful,
+            score_kind: kinds.get(&SCORE_KIND).expect("no geode robot"),
+        }
+    }
+
+    fn kinds(&self) -> usize {
+        self.costs.len()
+    }
+}
+
+/// Robots bought so far, one 4-bit entry per minute, indexed by the time
+/// remaining after the minute. Entries hold the kind plus one, zero for none.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+struct Schedule(u128);
+
+impl Schedule {
+    fn with_bought(self, time_remaining: u32, kind: usize) -> Self {
+        Self(self.0 | ((kind as u128 + 1) << (4 * time_remaining)))
+    }
+
+    fn bought(&self, time_remaining: u32) -> Option<usize> {
+        match (self.0 >> (4 * time_remaining)) & 0xf {
+            0 => None,
+            kind => Some(kind as usize - 1),
         }
     }
 }
@@ -126,16 +175,22 @@ struct SimState {
     time_remaining: u32,
     cash: Resources,
     bots: Resources,
+    /// Final score from the score robots bought so far, which are not kept
+    /// in `bots`.
     geodes: u32,
+    schedule: Schedule,
 }
 
 impl SimState {
-    pub const fn new(time_remaining: u32) -> Self {
+    pub fn new(time_remaining: u32) -> Self {
+        assert!(time_remaining <= MAX_MINUTES);
         Self {
             time_remaining,
-            cash: Resources::new(),
-            bots: Resources::new().add_ore(1),
+            cash: Resources::default(),
+            // one robot of the first kind to start with
+            bots: Resources::single(0, 1),
             geodes: 0,
+            schedule: Schedule::default(),
         }
     }
 
@@ -144,38 +199,24 @@ impl SimState {
         Self {
             time_remaining: self.time_remaining - n,
             cash: self.cash + self.bots * n,
-            bots: self.bots,
-            geodes: self.geodes,
+            ..*self
         }
     }
 
-    fn div_round_up(a: u32, b: u32) -> u32 {
-        (a + b - 1) / b
-    }
-
+    /// Minutes until a robot with the given cost is built, including the
+    /// minute it takes to build it, if it is done in time to be useful.
     pub fn can_afford_when(&self, cost: &Resources) -> Option<u32> {
         if self.time_remaining <= 1 {
             return None;
         }
-        if self.cash.can_buy(cost) {
-            return Some(1);
-        }
-        let ore_units = cost.ore.saturating_sub(self.cash.ore);
-        let clay_units = cost.clay.saturating_sub(self.cash.clay);
-        let obsidian_units = cost.obsidian.saturating_sub(self.cash.obsidian);
-        let mut max_time = Self::div_round_up(ore_units, self.bots.ore);
-        if clay_units > 0 {
-            if self.bots.clay == 0 {
-                return None;
-            } else {
-                max_time = max_time.max(Self::div_round_up(clay_units, self.bots.clay));
-            }
-        }
-        if obsidian_units > 0 {
-            if self.bots.obsidian == 0 {
-                return None;
-            } else {
-                max_time = max_time.max(Self::div_round_up(obsidian_units, self.bots.obsidian));
+        let mut max_time = 0;
+        for kind in 0..MAX_KINDS {
+            let missing = cost[kind].saturating_sub(self.cash[kind]);
+            if missing > 0 {
+                if self.bots[kind] == 0 {
+                    return None;
+                }
+                max_time = max_time.max(missing.div_ceil(self.bots[kind]));
             }
         }
         if max_time >= self.time_remaining - 1 {
@@ -185,88 +226,118 @@ impl SimState {
         }
     }
 
-    pub fn buy_ore_bot(&self, cost: &Resources) -> Self {
-        Self {
-            time_remaining: self.time_remaining,
-            cash: self.cash - *cost,
-            bots: self.bots.add_ore(1),
-            geodes: self.geodes,
+    /// Pay for a robot of `kind` at the end of the current minute.
+    pub fn buy(&self, blueprint: &Blueprint, kind: usize) -> Self {
+        let mut new = Self {
+            cash: self.cash - blueprint.costs[kind],
+            schedule: self.schedule.with_bought(self.time_remaining, kind),
+            ..*self
+        };
+        if kind == blueprint.score_kind {
+            new.geodes += self.time_remaining;
+        } else {
+            new.bots.0[kind] += 1;
         }
+        new
     }
+}
 
-    pub fn buy_clay_bot(&self, cost: &Resources) -> Self {
-        Self {
-            time_remaining: self.time_remaining,
-            cash: self.cash - *cost,
-            bots: self.bots.add_clay(1),
-            geodes: self.geodes,
-        }
+impl SearchState for SimState {
+    type Problem = Blueprint;
+
+    fn score(&self, _blueprint: &Blueprint) -> u32 {
+        self.geodes
     }
 
-    pub fn buy_obsidian_bot(&self, cost: &Resources) -> Self {
-        Self {
-            time_remaining: self.time_remaining,
-            cash: self.cash - *cost,
-            bots: self.bots.add_obsidian(1),
-            geodes: self.geodes,
-        }
+    fn bound(&self, _blueprint: &Blueprint) -> u32 {
+        // buying a score robot every remaining minute
+        self.geodes + self.time_remaining * (self.time_remaining + 1) / 2
     }
 
-    pub fn buy_geodes(&self, cost: &Resources) -> Self {
-        Self {
-            time_remaining: self.time_remaining,
-            cash: self.cash - *cost,
-            bots: self.bots,
-            geodes: self.geodes + self.time_remaining,
+    fn successors(&self, blueprint: &Blueprint, out: &mut Vec<Self>) {
+        // score robots first, then the more expensive kinds
+        let score_kind = blueprint.score_kind;
+        let others = (0..blueprint.kinds()).rev().filter(|&k| k != score_kind);
+        for kind in std::iter::once(score_kind).chain(others) {
+            if kind != score_kind && self.bots[kind] >= blueprint.max_useful[kind] {
+                continue;
+            }
+            if let Some(when) = self.can_afford_when(&blueprint.costs[kind]) {
+                out.push(self.steps(when).buy(blueprint, kind));
+            }
         }
     }
 }
 
-const TRIANGULAR_NUMBERS: [u32; 35] = [
-    0, 1, 3, 6, 10, 15, 21, 28, 36, 45, 55, 66, 78, 91, 105, 120, 136, 153, 171, 190, 210, 231,
-    253, 276, 300, 325, 351, 378, 406, 435, 465, 496, 528, 561, 595,
-];
-
-struct Simulation {
-    best_result: u32,
-    blueprint: Blueprint,
+/// Robots to build, as `(minute, kind)` with minutes counted from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildPlan {
+    pub time_limit: u32,
+    pub steps: Vec<(u32, usize)>,
 }
 
-impl Simulation {
-    pub fn simulate_step(&mut self, state: SimState) {
-        assert!(state.time_remaining > 0);
-        if state.geodes + TRIANGULAR_NUMBERS[state.time_remaining as usize] <= self.best_result {
-            return;
-        }
-        if let Some(when) = state.can_afford_when(&self.blueprint.geodes_cost) {
-            self.simulate_step(state.steps(when).buy_geodes(&self.blueprint.geodes_cost));
-        }
-        if let Some(when) = state.can_afford_when(&self.blueprint.obsidian_cost) {
-            self.simulate_step(
-                state
-                    .steps(when)
-                    .buy_obsidian_bot(&self.blueprint.obsidian_cost),
-            );
-        }
-        if let Some(when) = state.can_afford_when(&self.blueprint.clay_cost) {
-            self.simulate_step(state.steps(when).buy_clay_bot(&self.blueprint.clay_cost));
-        }
-        if state.bots.ore < self.blueprint.max_ore_required {
-            if let Some(when) = state.can_afford_when(&self.blueprint.ore_cost) {
-                self.simulate_step(state.steps(when).buy_ore_bot(&self.blueprint.ore_cost));
+impl BuildPlan {
+    fn from_schedule(schedule: Schedule, time_limit: u32) -> Self {
+        let steps = (0..time_limit)
+            .rev()
+            .filter_map(|remaining| {
+                let kind = schedule.bought(remaining)?;
+                Some((time_limit - remaining, kind))
+            })
+            .collect();
+        Self { time_limit, steps }
+    }
+
+    /// Play the plan minute by minute, describing what gets built and what we
+    /// have at the end of each minute.
+    pub fn timeline(&self, blueprint: &Blueprint) -> String {
+        let mut out = String::new();
+        let mut cash = Resources::default();
+        let mut bots = Resources::single(0, 1);
+        for minute in 1..=self.time_limit {
+            let built = self
+                .steps
+                .iter()
+                .find(|&&(step_minute, _)| step_minute == minute)
+                .map(|&(_, kind)| kind);
+            if let Some(kind) = built {
+                assert!(
+                    cash.can_buy(&blueprint.costs[kind]),
+                    "plan is not affordable"
+                );
+                cash = cash - blueprint.costs[kind];
             }
+            cash = cash + bots;
+            let action = match built {
+                Some(kind) => {
+                    bots.0[kind] += 1;
+                    format!("build {} robot", blueprint.names[kind])
+                }
+                None => "wait".to_string(),
+            };
+            writeln!(
+                out,
+                "minute {:2}: {:<24} have {}",
+                minute,
+                action,
+                cash.display(&blueprint.names)
+            )
+            .unwrap();
         }
-        self.best_result = self.best_result.max(state.geodes);
+        out
     }
+}
 
-    pub fn simulate(blueprint: Blueprint, time_limit: u32) -> u32 {
-        let mut sim = Self {
-            best_result: 0,
-            blueprint,
-        };
-        sim.simulate_step(SimState::new(time_limit));
-        sim.best_result
-    }
+/// Most geodes that can be opened, and how.
+pub fn best_plan(blueprint: &Blueprint, time_limit: u32) -> (u32, BuildPlan) {
+    let result = BranchAndBound::new(blueprint).search(SimState::new(time_limit));
+    let schedule = result
+        .best_state
+        .map_or(Schedule::default(), |s| s.schedule);
+    (
+        result.best_score,
+        BuildPlan::from_schedule(schedule, time_limit),
+    )
 }
 
 fn part1_dynamic_like(input: &mut dyn BufRead) -> String {
@@ -279,7 +350,7 @@ fn part1_dynamic_like(input: &mut dyn BufRead) -> String {
 
     blueprints
         .par_iter()
-        .map(|&blueprint| blueprint.id * Simulation::simulate(blueprint, TIME_LIMIT))
+        .map(|blueprint| blueprint.id * best_plan(blueprint, TIME_LIMIT).0)
         .sum::<u32>()
         .to_string()
 }
@@ -295,9 +366,37 @@ fn part2_more_steps_less_elephants(input: &mut dyn BufRead) -> String {
 
     blueprints
         .par_iter()
-        .map(|&blueprint| Simulation::simulate(blueprint, TIME_LIMIT))
+        .map(|blueprint| best_plan(blueprint, TIME_LIMIT).0)
         .product::<u32>()
         .to_string()
 }
 
 pub const SOLVERS: &[Solver] = &[part1_dynamic_like, part2_more_steps_less_elephants];
+
+#[allow(unused)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &[u8] = b"Blueprint 1: \
+        Each ore robot costs 4 ore. \
+        Each clay robot costs 2 ore. \
+        Each obsidian robot costs 3 ore and 14 clay. \
+        Each geode robot costs 2 ore and 7 obsidian.";
+
+    #[test]
+    fn test_build_plan() {
+        let blueprint = Blueprint::load(EXAMPLE);
+        assert_eq!(blueprint.names, ["ore", "clay", "obsidian", "geode"]);
+        assert_eq!(
+            blueprint.costs[2],
+            Resources::single(0, 3) + Resources::single(1, 14)
+        );
+
+        let (geodes, plan) = best_plan(&blueprint, 24);
+        assert_eq!(geodes, 9);
+        let timeline = plan.timeline(&blueprint);
+        let last = timeline.lines().last().unwrap();
+        assert!(last.starts_with("minute 24: "));
+        assert!(last.ends_with(", 9 geode"), "{}", timeline);
+    }
+}
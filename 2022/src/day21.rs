@@ -1,36 +1,24 @@
-use std::{cell::RefCell, collections::HashMap, io::BufRead};
+use std::{collections::HashMap, io::BufRead};
 
 use bstr::io::BufReadExt;
 
-use crate::{parse_num, Solver};
+use crate::{
+    parse::parse,
+    symbolic::{BinOp, Expression, NodeId, Rational, SolveError},
+    Solver,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub struct Monkey(u64);
+pub struct Monkey(u32);
 
 impl Monkey {
-    // if the monkey has an lowercase ascii name, it has the bits 0x60 set
-    const MONKEY_ASCII_MASK: u64 = 0x60_60_60_60;
-
     pub fn load_monkey(line: &[u8]) -> Self {
-        Self(u32::from_ne_bytes(line.try_into().unwrap()) as u64)
-    }
-
-    pub const fn is_monkey(&self) -> bool {
-        // 0x60
-        self.0 & Self::MONKEY_ASCII_MASK == Self::MONKEY_ASCII_MASK
+        Self(u32::from_ne_bytes(line.try_into().unwrap()))
     }
 
-    pub const fn is_number(&self) -> bool {
-        !self.is_monkey()
-    }
-
-    pub fn to_string(&self) -> String {
-        if self.is_monkey() {
-            let bytes = self.0.to_ne_bytes();
-            std::str::from_utf8(&bytes).unwrap().to_string()
-        } else {
-            self.0.to_string()
-        }
+    pub fn name(&self) -> String {
+        let bytes = self.0.to_ne_bytes();
+        std::str::from_utf8(&bytes).unwrap().to_string()
     }
 }
 
@@ -53,216 +41,312 @@ impl Operation {
         }
     }
 
-    pub fn apply(&self, left: u64, right: u64) -> u64 {
+    /// Checked arithmetic: `None` on overflow or division by zero.
+    pub fn apply(&self, left: i64, right: i64) -> Option<i64> {
         match self {
-            Self::Add => left + right,
-            Self::Sub => left - right,
-            Self::Mul => left * right,
-            Self::Div => left / right,
+            Self::Add => left.checked_add(right),
+            Self::Sub => left.checked_sub(right),
+            Self::Mul => left.checked_mul(right),
+            Self::Div => left.checked_div(right),
         }
     }
 
-    pub fn unapply(&self, result: u64, side: Side, left: u64, right: u64) -> u64 {
+    pub const fn symbolic(&self) -> BinOp {
         match self {
-            Self::Add => match side {
-                Side::Left => result - right,
-                Side::Right => result - left,
-            },
-            Self::Sub => match side {
-                Side::Left => result + right,
-                Side::Right => left - result,
-            },
-            Self::Mul => match side {
-                Side::Left => result / right,
-                Side::Right => result / left,
-            },
-            Self::Div => match side {
-                Side::Left => result * right,
-                Side::Right => left / result,
-            },
+            Self::Add => BinOp::Add,
+            Self::Sub => BinOp::Sub,
+            Self::Mul => BinOp::Mul,
+            Self::Div => BinOp::Div,
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct MonkeyOp {
-    left: Monkey,
-    right: Monkey,
-    op: Operation,
-    human_side: RefCell<Option<Side>>,
-    result: RefCell<Option<u64>>,
+/// What a monkey yells, with operands given as arena indices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Job {
+    Number(i64),
+    Op(Operation, usize, usize),
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub enum Side {
-    Left,
-    Right,
+/// Job as read from the input, before names are resolved to indices.
+#[derive(Clone, Copy)]
+enum RawJob {
+    Number(i64),
+    Op(Operation, Monkey, Monkey),
 }
 
-impl Side {
-    pub fn other(&self) -> Self {
-        match self {
-            Self::Left => Self::Right,
-            Self::Right => Self::Left,
-        }
-    }
+/// All monkeys in an arena, sorted topologically so that operands always come
+/// before the monkeys using them. Evaluation is a single forward pass, and
+/// changing a number only re-evaluates the monkeys that depend on it.
+#[derive(Debug, Clone)]
+pub struct MonkeyTree {
+    names: Vec<Monkey>,
+    jobs: Vec<Job>,
+    /// For each monkey, the monkeys using its value.
+    parents: Vec<Vec<usize>>,
+    index: HashMap<Monkey, usize>,
+    /// `None` where the calculation overflowed or divided by zero.
+    values: Vec<Option<i64>>,
 }
 
-impl MonkeyOp {
-    pub fn load(line: &[u8]) -> Self {
-        if line.len() == 4 + 3 + 4 {
-            Self {
-                left: Monkey::load_monkey(line[0..4].try_into().unwrap()),
-                right: Monkey::load_monkey(line[4 + 3..4 + 3 + 4].try_into().unwrap()),
-                op: Operation::from_char(line[5]),
-                human_side: RefCell::new(None),
-                result: RefCell::new(None),
+impl MonkeyTree {
+    pub fn load(input: &mut dyn BufRead) -> Self {
+        let raw = input
+            .byte_lines()
+            .flatten()
+            .map(|line| {
+                let monkey = Monkey::load_monkey(&line[..4]);
+                let job = &line[6..];
+                let job = if job.len() == 4 + 3 + 4 {
+                    RawJob::Op(
+                        Operation::from_char(job[5]),
+                        Monkey::load_monkey(&job[0..4]),
+                        Monkey::load_monkey(&job[7..11]),
+                    )
+                } else {
+                    RawJob::Number(parse(job))
+                };
+                (monkey, job)
+            })
+            .collect::<Vec<_>>();
+        let position = raw
+            .iter()
+            .enumerate()
+            .map(|(pos, &(monkey, _))| (monkey, pos))
+            .collect::<HashMap<_, _>>();
+        let lookup = |monkey: Monkey| {
+            *position
+                .get(&monkey)
+                .unwrap_or_else(|| panic!("unknown monkey {}", monkey.name()))
+        };
+
+        // iterative depth-first post-order in input order, so operands get
+        // lower indices and the layout does not depend on hashing
+        let mut names = Vec::with_capacity(raw.len());
+        let mut index = HashMap::with_capacity(raw.len());
+        let mut on_stack = vec![false; raw.len()];
+        for &(start, _) in &raw {
+            let mut stack = vec![(start, false)];
+            while let Some((monkey, expanded)) = stack.pop() {
+                if index.contains_key(&monkey) {
+                    continue;
+                }
+                let pos = lookup(monkey);
+                match raw[pos].1 {
+                    RawJob::Op(_, left, right) if !expanded => {
+                        assert!(!on_stack[pos], "monkey {} depends on itself", monkey.name());
+                        on_stack[pos] = true;
+                        stack.push((monkey, true));
+                        stack.push((right, false));
+                        stack.push((left, false));
+                    }
+                    _ => {
+                        on_stack[pos] = false;
+                        index.insert(monkey, names.len());
+                        names.push(monkey);
+                    }
+                }
             }
-        } else {
-            let number = parse_num(line);
-            Self::number(Monkey(number))
         }
-    }
 
-    pub const fn number(m: Monkey) -> Self {
-        assert!(m.is_number());
-        Self {
-            left: m,
-            right: Monkey(1),
-            op: Operation::Mul,
-            human_side: RefCell::new(None),
-            result: RefCell::new(Some(m.0)),
+        let jobs = names
+            .iter()
+            .map(|&m| match raw[lookup(m)].1 {
+                RawJob::Number(n) => Job::Number(n),
+                RawJob::Op(op, left, right) => Job::Op(op, index[&left], index[&right]),
+            })
+            .collect::<Vec<_>>();
+        let mut parents = vec![Vec::new(); jobs.len()];
+        for (idx, job) in jobs.iter().enumerate() {
+            if let Job::Op(_, left, right) = *job {
+                parents[left].push(idx);
+                if right != left {
+                    parents[right].push(idx);
+                }
+            }
         }
-    }
 
-    pub fn calculate(&self, others: &HashMap<Monkey, Self>) -> u64 {
-        let left = if self.left.is_monkey() {
-            others[&self.left].calculate(others)
-        } else {
-            self.left.0
-        };
-        let right = if self.right.is_monkey() {
-            others[&self.right].calculate(others)
-        } else {
-            self.right.0
+        let mut tree = Self {
+            names,
+            values: vec![None; jobs.len()],
+            jobs,
+            parents,
+            index,
         };
-        let result = self.op.apply(left, right);
-        self.result.replace(Some(result));
-        result
+        for idx in 0..tree.jobs.len() {
+            tree.values[idx] = tree.calculate(idx);
+        }
+        tree
     }
 
-    pub fn find_human(&self, human: Monkey, others: &HashMap<Monkey, Self>) -> Option<Side> {
-        if self.human_side.borrow().is_none() {
-            let human_side = if self.left == human {
-                Some(Side::Left)
-            } else if self.right == human {
-                Some(Side::Right)
-            } else {
-                let left_human = if self.left.is_monkey() {
-                    others[&self.left].find_human(human, others)
-                } else {
-                    None
-                };
-                let right_human = if self.right.is_monkey() {
-                    others[&self.right].find_human(human, others)
-                } else {
-                    None
-                };
-                if left_human.is_some() {
-                    Some(Side::Left)
-                } else if right_human.is_some() {
-                    Some(Side::Right)
-                } else {
-                    None
-                }
-            };
-            self.human_side.replace(human_side);
-        }
-        *self.human_side.borrow()
+    pub fn find(&self, name: &[u8]) -> Option<usize> {
+        self.index.get(&Monkey::load_monkey(name)).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.jobs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    pub fn name(&self, idx: usize) -> String {
+        self.names[idx].name()
+    }
+
+    /// Value of a monkey, or `None` if its calculation overflowed or divided
+    /// by zero.
+    pub fn value(&self, idx: usize) -> Option<i64> {
+        self.values[idx]
     }
 
-    pub fn left_result(&self, others: &HashMap<Monkey, Self>) -> u64 {
-        if self.left.is_number() {
-            self.left.0
-        } else {
-            others[&self.left].result.borrow().unwrap()
+    pub fn operands(&self, idx: usize) -> Option<(usize, usize)> {
+        match self.jobs[idx] {
+            Job::Number(_) => None,
+            Job::Op(_, left, right) => Some((left, right)),
         }
     }
 
-    pub fn right_result(&self, others: &HashMap<Monkey, Self>) -> u64 {
-        if self.right.is_number() {
-            self.right.0
-        } else {
-            others[&self.right].result.borrow().unwrap()
+    fn calculate(&self, idx: usize) -> Option<i64> {
+        match self.jobs[idx] {
+            Job::Number(n) => Some(n),
+            Job::Op(op, left, right) => op.apply(self.values[left]?, self.values[right]?),
         }
     }
 
-    pub fn get(&self, side: Side) -> Monkey {
-        match side {
-            Side::Left => self.left,
-            Side::Right => self.right,
+    /// Make the monkey at `idx` yell `number` and re-evaluate the monkeys
+    /// depending on it.
+    pub fn set_number(&mut self, idx: usize, number: i64) {
+        self.jobs[idx] = Job::Number(number);
+        self.values[idx] = Some(number);
+        // dependents all have higher indices, so visiting them in index order
+        // sees every operand updated before it is used
+        let mut dirty = self.parents[idx].clone();
+        dirty.sort_unstable();
+        let mut pos = 0;
+        while pos < dirty.len() {
+            let current = dirty[pos];
+            pos += 1;
+            let value = self.calculate(current);
+            if value == self.values[current] {
+                continue;
+            }
+            self.values[current] = value;
+            for &parent in &self.parents[current] {
+                // keep the pending part sorted and free of duplicates
+                if let Err(at) = dirty[pos..].binary_search(&parent) {
+                    dirty.insert(pos + at, parent);
+                }
+            }
         }
     }
 
-    pub fn human_value(&self, human: Monkey, others: &HashMap<Monkey, Self>, passdown: u64) -> u64 {
-        let left_result = self.left_result(others);
-        let right_result = self.right_result(others);
-        let side = self.human_side.borrow().unwrap();
-        let result = self.op.unapply(passdown, side, left_result, right_result);
-        if self.left == human || self.right == human {
-            result
-        } else {
-            let human_side = self.get(side);
-            others[&human_side].human_value(human, others, result)
+    /// Build the expressions of all monkeys with `var` as a variable, in
+    /// topological order. Returns the node of each monkey.
+    pub fn build_expression(
+        &self,
+        var: usize,
+        expr: &mut Expression,
+    ) -> Result<Vec<NodeId>, SolveError> {
+        let var_name = self.name(var);
+        let mut nodes: Vec<NodeId> = Vec::with_capacity(self.len());
+        for (idx, job) in self.jobs.iter().enumerate() {
+            let node = match *job {
+                _ if idx == var => expr.var(&var_name),
+                Job::Number(n) => expr.constant(Rational::from_int(n as i128)),
+                Job::Op(op, left, right) => {
+                    expr.binary(op.symbolic(), nodes[left], nodes[right])?
+                }
+            };
+            nodes.push(node);
         }
+        Ok(nodes)
     }
 }
 
 fn part1_monkey_tree(input: &mut dyn BufRead) -> String {
-    let monkeys = input
-        .byte_lines()
-        .flatten()
-        .map(|line| {
-            let monkey = Monkey::load_monkey(&line[..4]);
-            let op = MonkeyOp::load(&line[6..]);
-            (monkey, op)
-        })
-        .collect::<HashMap<_, _>>();
-
-    let root = Monkey::load_monkey(b"root");
-    monkeys[&root].calculate(&monkeys).to_string()
+    let tree = MonkeyTree::load(input);
+    let root = tree.find(b"root").unwrap();
+    tree.value(root).unwrap().to_string()
 }
 
 fn part2_human_value(input: &mut dyn BufRead) -> String {
-    let monkeys = input
-        .byte_lines()
-        .flatten()
-        .map(|line| {
-            let monkey = Monkey::load_monkey(&line[..4]);
-            let op = MonkeyOp::load(&line[6..]);
-            (monkey, op)
-        })
-        .collect::<HashMap<_, _>>();
-
-    let root = Monkey::load_monkey(b"root");
-    let root_monkey = &monkeys[&root];
-    // prepare results
-    root_monkey.calculate(&monkeys);
-    // find human side
-    let human = Monkey::load_monkey(b"humn");
-    root_monkey.find_human(human, &monkeys);
-    // calculate human value
-    let human_side = root_monkey.human_side.borrow().unwrap();
-    let human_side_monkey = &monkeys[&root_monkey.get(human_side)];
-    match human_side {
-        Side::Left => {
-            human_side_monkey.human_value(human, &monkeys, root_monkey.right_result(&monkeys))
-        }
-        Side::Right => {
-            human_side_monkey.human_value(human, &monkeys, root_monkey.left_result(&monkeys))
-        }
+    let tree = MonkeyTree::load(input);
+    let root = tree.find(b"root").unwrap();
+    let human = tree.find(b"humn").unwrap();
+    // root compares its two operands for equality
+    let (left, right) = tree.operands(root).unwrap();
+    let mut expr = Expression::new();
+    let nodes = tree.build_expression(human, &mut expr).unwrap();
+    let (left, right) = (nodes[left], nodes[right]);
+    match expr.solve_integer(left, right, &tree.name(human)) {
+        Ok(value) => value.to_string(),
+        Err(err) => panic!(
+            "cannot solve {} = {}: {}",
+            expr.display(left),
+            expr.display(right),
+            err
+        ),
     }
-    .to_string()
 }
 
 pub const SOLVERS: &[Solver] = &[part1_monkey_tree, part2_human_value];
+
+#[allow(unused)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &[u8] = b"root: pppw + sjmn
+dbpl: 5
+cczh: sllz + lgvd
+zczc: 2
+ptdq: humn - dvpt
+dvpt: 3
+lfqf: 4
+humn: 5
+ljgn: 2
+sjmn: drzm * dbpl
+sllz: 4
+pppw: cczh / lfqf
+lgvd: ljgn * ptdq
+drzm: hmdt - zczc
+hmdt: 32
+";
+
+    #[test]
+    fn test_what_if() {
+        let mut tree = MonkeyTree::load(&mut &EXAMPLE[..]);
+        let root = tree.find(b"root").unwrap();
+        let human = tree.find(b"humn").unwrap();
+        let (left, right) = tree.operands(root).unwrap();
+        assert_eq!(tree.value(root), Some(152));
+        assert_ne!(tree.value(left), tree.value(right));
+
+        tree.set_number(human, 301);
+        assert_eq!(tree.value(left), Some(150));
+        assert_eq!(tree.value(right), Some(150));
+        assert_eq!(tree.value(root), Some(300));
+
+        let fresh = MonkeyTree::load(&mut &EXAMPLE[..]);
+        tree.set_number(human, 5);
+        // the layout only depends on the input
+        assert!((0..tree.len())
+            .all(|idx| tree.name(idx) == fresh.name(idx) && tree.value(idx) == fresh.value(idx)));
+    }
+
+    #[test]
+    fn test_input_order_layout() {
+        let tree = MonkeyTree::load(&mut &EXAMPLE[..]);
+        // root comes first in the input, so its operands are laid out first
+        assert_eq!(tree.name(0), "sllz");
+        assert_eq!(tree.find(b"root"), Some(tree.len() - 1));
+    }
+
+    #[test]
+    #[should_panic(expected = "monkey aaaa depends on itself")]
+    fn test_cycle() {
+        let input = b"root: aaaa + bbbb\naaaa: bbbb * cccc\nbbbb: 3\ncccc: aaaa - bbbb\n";
+        MonkeyTree::load(&mut &input[..]);
+    }
+}
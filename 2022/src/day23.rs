@@ -1,25 +1,56 @@
 use std::io::BufRead;
 
 use crate::{
-    tiles::{Point, Tiles},
+    tiles::{CoordType, Point, Tiles},
     Solver,
 };
 
-const INPUT_SIZE: usize = 100 * 100;
-const EXPAND: usize = 70;
+/// Free tiles kept around the elves on every side; elves look one tile
+/// around them, move one tile and may push back another elf one more tile.
+const MARGIN: CoordType = 3;
 
 const N_SIDE: u8 = 0b1110_0000;
 const W_SIDE: u8 = 0b1001_0100;
 const S_SIDE: u8 = 0b0000_0111;
 const E_SIDE: u8 = 0b0010_1001;
 
-const SIDES: [u8; 4] = [N_SIDE, S_SIDE, W_SIDE, E_SIDE];
-const MOVES: [Point; 4] = [
-    Point::new(0, -1),
-    Point::new(0, 1),
-    Point::new(-1, 0),
-    Point::new(1, 0),
-];
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    North,
+    South,
+    West,
+    East,
+}
+
+impl Direction {
+    /// The order the elves consider directions in the first round.
+    pub const PUZZLE_ORDER: [Direction; 4] = [
+        Direction::North,
+        Direction::South,
+        Direction::West,
+        Direction::East,
+    ];
+
+    /// Neighbors that have to be free to move this way, as a mask for the
+    /// neighborhood computed in `Map::round`.
+    const fn side(self) -> u8 {
+        match self {
+            Direction::North => N_SIDE,
+            Direction::South => S_SIDE,
+            Direction::West => W_SIDE,
+            Direction::East => E_SIDE,
+        }
+    }
+
+    const fn step(self) -> Point {
+        match self {
+            Direction::North => Point::new(0, -1),
+            Direction::South => Point::new(0, 1),
+            Direction::West => Point::new(-1, 0),
+            Direction::East => Point::new(1, 0),
+        }
+    }
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 struct Entry {
@@ -33,82 +64,146 @@ impl Entry {
     }
 }
 
-struct Map {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RoundStats {
+    /// Rounds played so far, including this one.
+    pub round: usize,
+    /// Elves that moved in this round.
+    pub moved: usize,
+    pub elves: usize,
+    /// Top left and bottom right corner of the smallest rectangle containing
+    /// all elves after the round, in input coordinates; `None` without elves.
+    pub bounds: Option<(Point, Point)>,
+}
+
+impl RoundStats {
+    /// Tiles in the bounding rectangle without an elf.
+    pub fn empty_ground(&self) -> usize {
+        match self.bounds {
+            Some((min, max)) => {
+                let size = max - min + Point::new(1, 1);
+                size.x as usize * size.y as usize - self.elves
+            }
+            None => 0,
+        }
+    }
+}
+
+pub struct Map {
     map: Tiles<Entry>,
+    /// Position of the top left input tile in `map`.
+    origin: Point,
+    /// Bounding rectangle of all elves, in `map` coordinates.
     min_bounds: Point,
     max_bounds: Point,
+    /// Bounding rectangle of the elves that may move next round, in `map`
+    /// coordinates; empty if `min_bounds_moving` is not below the maximum.
     min_bounds_moving: Point,
     max_bounds_moving: Point,
+    elves: usize,
+    directions: Vec<Direction>,
     step: usize,
 }
 
 impl Map {
     pub fn new(source: Tiles<u8>) -> Self {
-        let modified = source
-            .entries
-            .iter()
-            .map(|c| match c {
-                b'#' => Entry::new(1, 0),
-                _ => Entry::new(0, 0),
-            })
-            .collect::<Vec<_>>();
-        let map = Tiles {
-            entries: modified,
-            entry_len: source.entry_len,
-            line_width: source.line_width,
-        };
-        let mut bigger_map = Tiles::new(
-            map.width() + EXPAND * 2,
-            map.height() + EXPAND * 2,
+        let origin = Point::new(MARGIN, MARGIN);
+        let mut map = Tiles::new(
+            source.width() + 2 * MARGIN as usize,
+            source.height() + 2 * MARGIN as usize,
             Entry::new(0, 0),
         );
-        bigger_map.copy(EXPAND, EXPAND, &map);
+        let mut elves = 0;
+        let mut min_bounds = Point::new(CoordType::MAX, CoordType::MAX);
+        let mut max_bounds = Point::new(CoordType::MIN, CoordType::MIN);
+        for y in 0..source.height() {
+            for x in 0..source.width() {
+                if source[(x, y)] == b'#' {
+                    let p = origin + Point::from((x, y));
+                    map[p] = Entry::new(1, 1);
+                    elves += 1;
+                    min_bounds = min_bounds.min_bound(p);
+                    max_bounds = max_bounds.max_bound(p);
+                }
+            }
+        }
 
         Self {
-            map: bigger_map,
-            min_bounds: Point::from((EXPAND, EXPAND)),
-            max_bounds: Point::from((EXPAND + source.width(), EXPAND + source.height())),
-            min_bounds_moving: Point::from((EXPAND, EXPAND)),
-            max_bounds_moving: Point::from((EXPAND + source.width(), EXPAND + source.height())),
+            map,
+            origin,
+            min_bounds,
+            max_bounds,
+            min_bounds_moving: min_bounds,
+            max_bounds_moving: max_bounds,
+            elves,
+            directions: Direction::PUZZLE_ORDER.to_vec(),
             step: 0,
         }
     }
 
-    fn print(&self) {
-        for y in self.min_bounds.y - 1..=self.max_bounds.y + 1 {
-            for x in self.min_bounds.x - 1..=self.max_bounds.x + 1 {
-                let p = Point::from((x, y));
-                if self.map[p].current == 0 {
-                    print!(".");
-                } else {
-                    print!("#");
-                }
-            }
-            println!();
+    /// Consider `directions` in this order in the first round, rotating by
+    /// one every round.
+    pub fn with_directions(mut self, directions: &[Direction]) -> Self {
+        assert!(!directions.is_empty(), "elves need somewhere to go");
+        self.directions = directions.to_vec();
+        self
+    }
+
+    /// Copy the map into a larger one if the elves get close to its edge.
+    fn grow_if_needed(&mut self) {
+        let size = self.map.size();
+        if self.min_bounds.x >= MARGIN
+            && self.min_bounds.y >= MARGIN
+            && self.max_bounds.x < size.x - MARGIN
+            && self.max_bounds.y < size.y - MARGIN
+        {
+            return;
+        }
+        // grow by half on every side, so that growing is rare
+        let pad = size.x.max(size.y) / 2 + MARGIN;
+        let mut bigger_map = Tiles::new(
+            (size.x + 2 * pad) as usize,
+            (size.y + 2 * pad) as usize,
+            Entry::new(0, 0),
+        );
+        bigger_map.copy(pad as usize, pad as usize, &self.map);
+        self.map = bigger_map;
+
+        let shift = Point::new(pad, pad);
+        self.origin = self.origin + shift;
+        self.min_bounds = self.min_bounds + shift;
+        self.max_bounds = self.max_bounds + shift;
+        if self.min_bounds_moving.x <= self.max_bounds_moving.x {
+            self.min_bounds_moving = self.min_bounds_moving + shift;
+            self.max_bounds_moving = self.max_bounds_moving + shift;
         }
     }
 
-    fn round(&mut self) -> bool {
-        let mut moved = false;
-        let mut min_bounds_moving = Point::new(i16::MAX, i16::MAX);
-        let mut max_bounds_moving = Point::new(0, 0);
+    fn neighbors(&self, p: Point) -> u8 {
+        (self.map[Point::new(p.x - 1, p.y - 1)].current << 7)
+            + (self.map[Point::new(p.x, p.y - 1)].current << 6)
+            + (self.map[Point::new(p.x + 1, p.y - 1)].current << 5)
+            + (self.map[Point::new(p.x - 1, p.y)].current << 4)
+            + (self.map[Point::new(p.x + 1, p.y)].current << 3)
+            + (self.map[Point::new(p.x - 1, p.y + 1)].current << 2)
+            + (self.map[Point::new(p.x, p.y + 1)].current << 1)
+            + self.map[Point::new(p.x + 1, p.y + 1)].current
+    }
+
+    pub fn round(&mut self) -> RoundStats {
+        self.grow_if_needed();
+        let mut min_bounds_moving = Point::new(CoordType::MAX, CoordType::MAX);
+        let mut max_bounds_moving = Point::new(CoordType::MIN, CoordType::MIN);
 
-        for y in self.min_bounds_moving.y - 1..=self.max_bounds_moving.y + 1 {
-            for x in self.min_bounds_moving.x - 1..=self.max_bounds_moving.x + 1 {
-                let cur_point = Point::from((x, y));
+        // only elves next to one that moved last round may have neighbors
+        let (scan_min, scan_max) = (self.min_bounds_moving, self.max_bounds_moving);
+        for y in scan_min.y - 1..=scan_max.y + 1 {
+            for x in scan_min.x - 1..=scan_max.x + 1 {
+                let cur_point = Point::new(x, y);
                 if self.map[cur_point].current == 0 {
                     continue;
                 }
-                // find neighborhood for this point
-                let neighbors: u8 =
-                    (self.map[Point::new(cur_point.x - 1, cur_point.y - 1)].current << 7)
-                        + (self.map[Point::new(cur_point.x, cur_point.y - 1)].current << 6)
-                        + (self.map[Point::new(cur_point.x + 1, cur_point.y - 1)].current << 5)
-                        + (self.map[Point::new(cur_point.x - 1, cur_point.y)].current << 4)
-                        + (self.map[Point::new(cur_point.x + 1, cur_point.y)].current << 3)
-                        + (self.map[Point::new(cur_point.x - 1, cur_point.y + 1)].current << 2)
-                        + (self.map[Point::new(cur_point.x, cur_point.y + 1)].current << 1)
-                        + self.map[Point::new(cur_point.x + 1, cur_point.y + 1)].current;
+                let neighbors = self.neighbors(cur_point);
                 // by default, stay in place
                 self.map[cur_point].next = 1;
                 // if there are no neighbors, this point does not move
@@ -116,24 +211,25 @@ impl Map {
                     continue;
                 }
                 // update bounds because this is a candidate for move
-                moved = true;
                 min_bounds_moving = min_bounds_moving.min_bound(cur_point);
                 max_bounds_moving = max_bounds_moving.max_bound(cur_point);
-                for i in 0..4 {
-                    // start at the side matching the current step
-                    let i = (i + self.step) % 4;
-                    let side = SIDES[i];
-                    if neighbors & side != 0 {
+                for i in 0..self.directions.len() {
+                    // start at the direction matching the current step
+                    let dir = self.directions[(i + self.step) % self.directions.len()];
+                    if neighbors & dir.side() != 0 {
                         // side is occupied, try another
                         continue;
                     }
-                    let next_point = cur_point + MOVES[i];
+                    let next_point = cur_point + dir.step();
+                    // elves next to the new position may have to move next round
+                    min_bounds_moving = min_bounds_moving.min_bound(next_point);
+                    max_bounds_moving = max_bounds_moving.max_bound(next_point);
                     if self.map[next_point].next == 1 {
                         // if someone is already moving here, this point does not move and
                         // the other point is pushed back
                         self.map[cur_point].next = 1;
                         self.map[next_point].next = 0;
-                        self.map[next_point + MOVES[i]].next = 1;
+                        self.map[next_point + dir.step()].next = 1;
                     } else {
                         // move in this direction
                         self.map[cur_point].next = 0;
@@ -143,47 +239,154 @@ impl Map {
                 }
             }
         }
+
+        // moves end at most one tile outside the scanned area
+        let mut moved = 0;
+        for y in scan_min.y - 2..=scan_max.y + 2 {
+            for x in scan_min.x - 2..=scan_max.x + 2 {
+                let p = Point::new(x, y);
+                if self.map[p].current == 1 && self.map[p].next == 0 {
+                    moved += 1;
+                }
+                self.map[p].current = self.map[p].next;
+            }
+        }
         self.min_bounds_moving = min_bounds_moving;
         self.max_bounds_moving = max_bounds_moving;
 
-        for y in self.min_bounds_moving.y - 1..=self.max_bounds_moving.y + 1 {
-            for x in self.min_bounds_moving.x - 1..=self.max_bounds_moving.x + 1 {
-                let p = Point::from((x, y));
-                self.map[p].current = self.map[p].next;
+        // every elf moved at most one tile
+        let (old_min, old_max) = (self.min_bounds, self.max_bounds);
+        self.min_bounds = Point::new(CoordType::MAX, CoordType::MAX);
+        self.max_bounds = Point::new(CoordType::MIN, CoordType::MIN);
+        for y in old_min.y - 1..=old_max.y + 1 {
+            for x in old_min.x - 1..=old_max.x + 1 {
+                let p = Point::new(x, y);
+                if self.map[p].current == 1 {
+                    self.min_bounds = self.min_bounds.min_bound(p);
+                    self.max_bounds = self.max_bounds.max_bound(p);
+                }
             }
         }
 
-        self.min_bounds = self.min_bounds.min_bound(self.min_bounds_moving);
-        self.max_bounds = self.max_bounds.max_bound(self.max_bounds_moving);
         self.step += 1;
-        moved
+        RoundStats {
+            round: self.step,
+            moved,
+            elves: self.elves,
+            bounds: (self.elves > 0)
+                .then(|| (self.min_bounds - self.origin, self.max_bounds - self.origin)),
+        }
     }
-}
-
-pub fn part1_ten_rounds(input: &mut dyn BufRead) -> String {
-    let input_map = Tiles::load(input, INPUT_SIZE);
-    let mut map = Map::new(input_map);
 
-    for _ in 0..10 {
-        map.round();
+    /// Play rounds forever.
+    pub fn rounds(&mut self) -> impl Iterator<Item = RoundStats> + '_ {
+        std::iter::from_fn(move || Some(self.round()))
     }
 
-    let mut spaces = 0;
-    for y in map.min_bounds.y..=map.max_bounds.y {
-        for x in map.min_bounds.x..=map.max_bounds.x {
-            spaces += 1 - map.map[(x as usize, y as usize)].current as usize;
+    /// The elves in their bounding rectangle, like the puzzle draws them.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for y in self.min_bounds.y..=self.max_bounds.y {
+            for x in self.min_bounds.x..=self.max_bounds.x {
+                out.push(if self.map[Point::new(x, y)].current == 0 {
+                    '.'
+                } else {
+                    '#'
+                });
+            }
+            out.push('\n');
         }
+        out
     }
+}
 
-    spaces.to_string()
+pub fn part1_ten_rounds(input: &mut dyn BufRead) -> String {
+    let mut map = Map::new(Tiles::load(input, 0));
+    let stats = map.rounds().nth(9).unwrap();
+    stats.empty_ground().to_string()
 }
 
 pub fn part2_move_until_done(input: &mut dyn BufRead) -> String {
-    let input_map = Tiles::load(input, INPUT_SIZE);
-    let mut map = Map::new(input_map);
-
-    while map.round() {}
-    map.step.to_string()
+    let mut map = Map::new(Tiles::load(input, 0));
+    let stats = map.rounds().find(|stats| stats.moved == 0).unwrap();
+    stats.round.to_string()
 }
 
 pub const SOLVERS: &[Solver] = &[part1_ten_rounds, part2_move_until_done];
+
+#[allow(unused)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &[u8] = b"\
+....#..
+..###.#
+#...#.#
+.#...##
+#.###..
+##.#.##
+.#..#..
+";
+
+    #[test]
+    fn test_example() {
+        assert_eq!(part1_ten_rounds(&mut &EXAMPLE[..]), "110");
+        assert_eq!(part2_move_until_done(&mut &EXAMPLE[..]), "20");
+
+        let mut map = Map::new(Tiles::load(&mut &EXAMPLE[..], 0));
+        let stats = map.rounds().take(10).collect::<Vec<_>>();
+        assert_eq!(
+            stats[9].bounds,
+            Some((Point::new(-2, -2), Point::new(9, 8)))
+        );
+        assert_eq!(
+            map.render(),
+            "\
+......#.....
+..........#.
+.#.#..#.....
+.....#......
+..#.....#..#
+#......##...
+....##......
+.#........#.
+...#.#..#...
+............
+...#..#..#..
+"
+        );
+    }
+
+    #[test]
+    fn test_growing_and_directions() {
+        // a small map that has to grow many times
+        let small = b".....\n..##.\n..#..\n.....\n..##.\n.....\n";
+        let mut map = Map::new(Tiles::load(&mut &small[..], 0));
+        let last = map.rounds().find(|stats| stats.moved == 0).unwrap();
+        assert_eq!(last.round, 4);
+        assert_eq!(map.render(), "..#..\n....#\n#....\n....#\n.....\n..#..\n");
+
+        let mut plain = Map::new(Tiles::load(&mut &b"#\n#\n"[..], 0));
+        let stats = plain.round();
+        assert_eq!(stats.bounds, Some((Point::new(0, -1), Point::new(0, 2))));
+
+        let mut east_first = Map::new(Tiles::load(&mut &b"#\n#\n"[..], 0))
+            .with_directions(&[Direction::East, Direction::West]);
+        let stats = east_first.round();
+        assert_eq!(stats.moved, 2);
+        assert_eq!(stats.bounds, Some((Point::new(1, 0), Point::new(1, 1))));
+        // still next to each other, so now they try west first
+        let stats = east_first.round();
+        assert_eq!(stats.bounds, Some((Point::new(0, 0), Point::new(0, 1))));
+
+        // without elves, nothing moves and there is no ground to count
+        let no_elves = b"...\n...\n";
+        let mut empty = Map::new(Tiles::load(&mut &no_elves[..], 0));
+        let stats = empty.round();
+        assert_eq!((stats.moved, stats.bounds), (0, None));
+        assert_eq!(stats.empty_ground(), 0);
+        assert_eq!(empty.render(), "");
+        assert_eq!(part1_ten_rounds(&mut &no_elves[..]), "0");
+        assert_eq!(part2_move_until_done(&mut &no_elves[..]), "1");
+    }
+}
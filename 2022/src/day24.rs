@@ -1,114 +1,126 @@
 use std::io::BufRead;
 
-use bit_set::BitSet;
-use bit_vec::BitVec;
-
 use crate::{
+    bitgrid::BitGrid,
     tiles::{Point, Tiles},
     Solver,
 };
 
-struct Blizzards {
-    width: usize,
-    height: usize,
-    elves: Vec<BitVec>,
-    blizzards_up: Vec<BitVec>,
-    blizzards_down: Vec<BitVec>,
-    blizzards_left: Vec<BitVec>,
-    blizzards_right: Vec<BitVec>,
+const UP: Point = Point::new(0, -1);
+const DOWN: Point = Point::new(0, 1);
+const LEFT: Point = Point::new(-1, 0);
+const RIGHT: Point = Point::new(1, 0);
+
+#[derive(Clone)]
+pub struct Blizzards {
+    elves: BitGrid,
+    blizzards_up: BitGrid,
+    blizzards_down: BitGrid,
+    blizzards_left: BitGrid,
+    blizzards_right: BitGrid,
     steps: usize,
 }
 
 impl Blizzards {
     pub fn has_elf(&self, p: Point) -> bool {
-        self.elves
-            .get(p.y as usize)
-            .map(|v| v.get(p.x as usize).unwrap_or(false))
-            .unwrap_or(false)
+        self.elves.get(p)
     }
 
     pub fn new(input: &Tiles<u8>) -> Self {
         let width = input.width() - 2;
         let height = input.height() - 2;
-        let elves = vec![BitVec::with_capacity(width); height];
-        let new_elves = elves.clone();
-        let mut blizzards_up = vec![BitVec::with_capacity(height); width];
-        let mut blizzards_down = vec![BitVec::with_capacity(height); width];
-        let mut blizzards_left = vec![BitVec::with_capacity(width); height];
-        let mut blizzards_right = vec![BitVec::with_capacity(width); height];
-        for y in 1..input.height() - 1 {
-            for x in 1..input.width() - 1 {
-                match input[(x, y)] {
-                    b'>' => blizzards_right[y - 1].set(x - 1, true),
-                    b'<' => blizzards_left[y - 1].set(x - 1, true),
-                    b'^' => blizzards_up[x - 1].set(y - 1, true),
-                    b'v' => blizzards_down[x - 1].set(y - 1, true),
-                    _ => (),
-                };
-            }
-        }
+        // strip the walls
+        let blizzards = |c| BitGrid::from_fn(width, height, |p| input[p + Point::new(1, 1)] == c);
         Self {
-            elves,
-            width,
-            height,
-            blizzards_up,
-            blizzards_down,
-            blizzards_left,
-            blizzards_right,
+            elves: BitGrid::new(width, height),
+            blizzards_up: blizzards(b'^'),
+            blizzards_down: blizzards(b'v'),
+            blizzards_left: blizzards(b'<'),
+            blizzards_right: blizzards(b'>'),
             steps: 0,
         }
     }
 
-    pub fn step(&mut self, spawn: Point) {
-        new_elves = vec![BitVec::with_capacity(self.width); self.height];
+    fn blizzards(&self) -> BitGrid {
+        let mut all = &self.blizzards_up | &self.blizzards_down;
+        all |= &self.blizzards_left;
+        all |= &self.blizzards_right;
+        all
+    }
+
+    fn move_blizzards(&mut self) {
         self.steps += 1;
-        for (idx, _) in self.elves.entries.iter().enumerate().filter(|(_, &v)| v) {
-            let p = self.elves.coords_for(idx);
-            if !self.has_blizzard(p) {
-                self.new_elves[p] = true;
-            }
-            for np in p.neighbors() {
-                if !self.has_blizzard(np) {
-                    self.new_elves[np] = true;
-                }
-            }
-        }
-        if !self.has_blizzard(spawn) {
-            self.new_elves[spawn] = true;
+        self.blizzards_up.rotate(UP);
+        self.blizzards_down.rotate(DOWN);
+        self.blizzards_left.rotate(LEFT);
+        self.blizzards_right.rotate(RIGHT);
+    }
+
+    pub fn step(&mut self, spawn: Point) {
+        self.move_blizzards();
+
+        // every elf either waits or moves to any of its neighbors
+        let mut new_elves = self.elves.shifted_union(&Point::CARDINAL_DIRECTIONS);
+        new_elves |= &self.elves;
+        if new_elves.contains(spawn) {
+            new_elves.set(spawn, true);
         }
-        std::mem::swap(&mut self.elves, &mut self.new_elves);
+        new_elves.difference_with(&self.blizzards());
+        self.elves = new_elves;
+    }
+
+    /// The position just outside the valley next to `p`, which must be on the
+    /// edge of the valley.
+    pub fn outside(&self, p: Point) -> Point {
+        [UP, DOWN, LEFT, RIGHT]
+            .into_iter()
+            .map(|dir| p + dir)
+            .find(|&q| !self.elves.contains(q))
+            .expect("point is not on the edge")
     }
 
     pub fn clear_elves(&mut self) {
-        self.elves.entries.fill(false);
+        self.elves.clear();
     }
 
-    #[allow(unused)]
-    pub fn print(&self) {
-        for y in 0..self.elves.height() {
-            for x in 0..self.elves.width() {
-                let p = Point::from((x, y));
-                let bits = self.blizzard_bits(p);
-                let ch = if self.has_elf(p) {
+    fn blizzard_bits(&self, p: Point) -> u8 {
+        (self.blizzards_up.get(p) as u8) << 3
+            | (self.blizzards_down.get(p) as u8) << 2
+            | (self.blizzards_left.get(p) as u8) << 1
+            | self.blizzards_right.get(p) as u8
+    }
+
+    /// Draw the valley like the puzzle does, including walls with gaps at
+    /// `openings` (positions just outside the valley), and an expedition at
+    /// `elf` if given. Elves of the reachability search are not drawn.
+    pub fn render(&self, openings: &[Point], elf: Option<Point>) -> String {
+        let mut out = String::new();
+        for y in -1..=self.elves.height() as i16 {
+            for x in -1..=self.elves.width() as i16 {
+                let p = Point::new(x, y);
+                let ch = if elf == Some(p) {
                     'E'
+                } else if !self.elves.contains(p) {
+                    if openings.contains(&p) {
+                        '.'
+                    } else {
+                        '#'
+                    }
                 } else {
-                    match bits {
+                    match self.blizzard_bits(p) {
                         0 => '.',
                         0b1000 => '^',
                         0b0100 => 'v',
                         0b0010 => '<',
                         0b0001 => '>',
-                        x if x.count_ones() == 2 => '2',
-                        x if x.count_ones() == 3 => '3',
-                        x if x.count_ones() == 4 => '4',
-                        _ => '?',
+                        bits => char::from_digit(bits.count_ones(), 10).unwrap(),
                     }
                 };
-                print!("{}", ch);
+                out.push(ch);
             }
-            println!();
+            out.push('\n');
         }
-        println!("---");
+        out
     }
 }
 
@@ -138,76 +150,158 @@ fn convert_map(input: &mut dyn BufRead) -> (Blizzards, Point, Point) {
     (map, start, end)
 }
 
-// fn step(spawn: Point, map: &Tiles<u8>, newmap: &mut Tiles<u8>) {
-//     for y in 0..map.height() {
-//         for x in 0..map.width() {
-//             let p = Point::from((x, y));
-//             for (i, dir) in Point::CARDINAL_DIRECTIONS.iter().enumerate() {
-//                 let mask = 1 << i;
-//                 if map[p] & mask != 0 {
-//                     let np = (Point::from((x, y)) + *dir) % map.size();
-//                     newmap[np] |= mask;
-//                 }
-//             }
-//         }
-//     }
-
-//     for y in 0..map.height() {
-//         for x in 0..map.width() {
-//             let p = Point::from((x, y));
-//             if map[p] & ELF_BIT == 0 {
-//                 continue;
-//             }
-//             if matches!(newmap.get(p), Some(0)) {
-//                 newmap[p] = ELF_BIT;
-//             }
-//             for dir in Point::CARDINAL_DIRECTIONS.iter() {
-//                 let np = Point::from((x, y)) + *dir;
-//                 if matches!(newmap.get(np), Some(0)) {
-//                     newmap[np] = ELF_BIT;
-//                 }
-//             }
-//         }
-//     }
-//     if newmap[spawn] == 0 {
-//         newmap[spawn] = ELF_BIT;
-//     }
-// }
+/// Minutes taken for each leg of a trip, and optionally where the expedition
+/// was at every minute.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Route {
+    pub leg_minutes: Vec<usize>,
+    /// Position at each minute from 0, including the entrance and exit
+    /// positions outside the valley.
+    pub path: Option<Vec<Point>>,
+}
+
+impl Route {
+    pub fn total_minutes(&self) -> usize {
+        self.leg_minutes.iter().sum()
+    }
+}
+
+/// Travel from `start` to each waypoint in turn, as fast as possible. Points
+/// are the positions in the valley next to the gaps in the wall; each leg ends
+/// one minute after reaching them, when stepping out of the valley.
+pub fn find_route(
+    mut map: Blizzards,
+    start: Point,
+    waypoints: &[Point],
+    record_path: bool,
+) -> Route {
+    let mut spawn = start;
+    let mut leg_minutes = Vec::with_capacity(waypoints.len());
+    let mut path = record_path.then(|| vec![map.outside(start)]);
+
+    for &dest in waypoints {
+        let leg_start = map.steps;
+        // reachable positions after each minute of the leg
+        let mut history = Vec::new();
+        loop {
+            map.step(spawn);
+            if record_path {
+                history.push(map.elves.clone());
+            }
+            if map.has_elf(dest) {
+                break;
+            }
+        }
+
+        if let Some(path) = &mut path {
+            // walk back through the reachable positions
+            let entrance = map.outside(spawn);
+            let mut pos = dest;
+            let mut leg = vec![pos];
+            for before in history.iter().rev().skip(1) {
+                if pos != entrance {
+                    pos = [pos, pos + UP, pos + DOWN, pos + LEFT, pos + RIGHT]
+                        .into_iter()
+                        .find(|&p| before.get(p))
+                        .unwrap_or(entrance);
+                }
+                leg.push(pos);
+            }
+            path.extend(leg.into_iter().rev());
+            path.push(map.outside(dest));
+        }
+
+        map.clear_elves();
+        // count one additional step to get to the actual destination outside the map
+        map.step(Point::new(-5, -5));
+        leg_minutes.push(map.steps - leg_start);
+        spawn = dest;
+    }
+
+    Route { leg_minutes, path }
+}
+
+/// Frames of the valley at every minute of `path`, starting from `map`.
+pub fn render_path(map: &Blizzards, openings: &[Point], path: &[Point]) -> Vec<String> {
+    let mut map = map.clone();
+    path.iter()
+        .map(|&elf| {
+            let frame = map.render(openings, Some(elf));
+            map.move_blizzards();
+            frame
+        })
+        .collect()
+}
 
 fn part1_quantum_elves(input: &mut dyn BufRead) -> String {
     let (map, start, end) = convert_map(input);
-    let destinations = &[end];
-    routefinder(map, start, destinations).to_string()
+    find_route(map, start, &[end], false)
+        .total_minutes()
+        .to_string()
 }
 
 fn part2_forgetful_elves(input: &mut dyn BufRead) -> String {
     let (map, start, end) = convert_map(input);
-    let destinations = &[end, start, end];
-    routefinder(map, start, destinations).to_string()
+    find_route(map, start, &[end, start, end], false)
+        .total_minutes()
+        .to_string()
 }
 
-fn routefinder(mut map: Blizzards, mut spawn: Point, destinations: &[Point]) -> usize {
-    let mut destinations = destinations.iter().copied().peekable();
-
-    loop {
-        map.step(spawn);
-        // println!("after step {}:", map.steps);
-        // map.print();
-        let dest = destinations.peek().unwrap();
-        if map.has_elf(*dest) {
-            spawn = destinations.next().unwrap();
-            // clear elves from the map
-            map.clear_elves();
-            // count one additional step to get to the actual destination outside the map
-            map.step(Point::new(-5, -5));
-            // if no next destination, we're done
-            if destinations.peek().is_none() {
-                break;
+pub const SOLVERS: &[Solver] = &[part1_quantum_elves, part2_forgetful_elves];
+
+#[allow(unused)]
+mod test {
+    use bstr::ByteSlice;
+
+    use super::*;
+
+    const EXAMPLE: &[u8] = b"#.######
+#>>.<^<#
+#.<..<<#
+#>v.><>#
+#<^v^^>#
+######.#
+";
+
+    #[test]
+    fn test_route_and_path() {
+        let (map, start, end) = convert_map(&mut &EXAMPLE[..]);
+        let initial = map.clone();
+        let route = find_route(map, start, &[end, start, end], true);
+        assert_eq!(route.leg_minutes, [18, 23, 13]);
+
+        let path = route.path.unwrap();
+        assert_eq!(path.len(), 18 + 23 + 13 + 1);
+        let openings = [initial.outside(start), initial.outside(end)];
+        assert_eq!(path[0], openings[0]);
+        assert_eq!(path[18], openings[1]);
+        assert_eq!(path[18 + 23], openings[0]);
+
+        let frames = render_path(&initial, &openings, &path);
+        assert_eq!(
+            frames[0].as_bytes(),
+            EXAMPLE.replacen(&b"#."[..], &b"#E"[..], 1).as_slice()
+        );
+        // one move at a time, never into a blizzard or wall
+        for (minute, frame) in frames.iter().enumerate() {
+            assert_eq!(frame.matches('E').count(), 1, "{}", frame);
+            if minute > 0 {
+                let step = path[minute] - path[minute - 1];
+                assert!(step.x.abs() + step.y.abs() <= 1);
             }
         }
-    }
+        // blizzards after minute 1 as drawn in the puzzle
+        let mut map = initial.clone();
+        map.move_blizzards();
+        assert_eq!(
+            map.render(&openings, None),
+            "#.######\n#.>3.<.#\n#<..<<.#\n#>2.22.#\n#>v..^<#\n######.#\n"
+        );
 
-    map.steps
+        let mut map = initial.clone();
+        for &p in &path {
+            assert!(map.blizzard_bits(p) == 0);
+            map.move_blizzards();
+        }
+    }
 }
-
-pub const SOLVERS: &[Solver] = &[part1_quantum_elves, part2_forgetful_elves];
@@ -0,0 +1,305 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, VecDeque},
+    fmt::Write,
+    hash::Hash,
+};
+
+use crate::tiles::Tiles;
+
+/// Distance between nodes without a path in between.
+pub const UNREACHABLE: u32 = u32::MAX;
+
+/// Assigns consecutive indices to names, in order of first appearance.
+#[derive(Debug, Clone)]
+pub struct Interner<K> {
+    names: Vec<K>,
+    index: HashMap<K, usize>,
+}
+
+impl<K> Default for Interner<K> {
+    fn default() -> Self {
+        Self {
+            names: Vec::new(),
+            index: HashMap::new(),
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone> Interner<K> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Index of `name`, adding it if it is new.
+    pub fn intern(&mut self, name: K) -> usize {
+        if let Some(&idx) = self.index.get(&name) {
+            return idx;
+        }
+        self.names.push(name.clone());
+        self.index.insert(name, self.names.len() - 1);
+        self.names.len() - 1
+    }
+
+    pub fn get(&self, name: &K) -> Option<usize> {
+        self.index.get(name).copied()
+    }
+
+    pub fn name(&self, idx: usize) -> &K {
+        &self.names[idx]
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+/// Directed graph with weighted edges, stored as adjacency lists. Undirected
+/// graphs have every edge in both directions.
+#[derive(Debug, Clone, Default)]
+pub struct Graph {
+    edges: Vec<Vec<(usize, u32)>>,
+}
+
+impl Graph {
+    pub fn new(nodes: usize) -> Self {
+        Self {
+            edges: vec![Vec::new(); nodes],
+        }
+    }
+
+    /// Build a graph from a distance matrix such as the one returned by
+    /// `adjacency_matrix`, skipping the diagonal and unreachable entries.
+    pub fn from_matrix(matrix: &Tiles<u32>) -> Self {
+        let mut graph = Self::new(matrix.width());
+        for from in 0..matrix.width() {
+            for to in 0..matrix.width() {
+                let weight = matrix[(from, to)];
+                if from != to && weight != UNREACHABLE {
+                    graph.add_edge(from, to, weight);
+                }
+            }
+        }
+        graph
+    }
+
+    pub fn len(&self) -> usize {
+        self.edges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.edges.is_empty()
+    }
+
+    pub fn add_node(&mut self) -> usize {
+        self.edges.push(Vec::new());
+        self.edges.len() - 1
+    }
+
+    pub fn add_edge(&mut self, from: usize, to: usize, weight: u32) {
+        self.edges[from].push((to, weight));
+    }
+
+    pub fn add_undirected_edge(&mut self, a: usize, b: usize, weight: u32) {
+        self.add_edge(a, b, weight);
+        self.add_edge(b, a, weight);
+    }
+
+    /// Outgoing edges of `node` as `(destination, weight)`.
+    pub fn neighbors(&self, node: usize) -> &[(usize, u32)] {
+        &self.edges[node]
+    }
+
+    /// Number of edges from `start` to each node, ignoring weights.
+    pub fn bfs(&self, start: usize) -> Vec<u32> {
+        let mut distances = vec![UNREACHABLE; self.len()];
+        let mut queue = VecDeque::from([start]);
+        distances[start] = 0;
+        while let Some(node) = queue.pop_front() {
+            for &(next, _) in self.neighbors(node) {
+                if distances[next] == UNREACHABLE {
+                    distances[next] = distances[node] + 1;
+                    queue.push_back(next);
+                }
+            }
+        }
+        distances
+    }
+
+    /// Length of the shortest path from `start` to each node.
+    pub fn dijkstra(&self, start: usize) -> Vec<u32> {
+        let mut distances = vec![UNREACHABLE; self.len()];
+        let mut queue = BinaryHeap::from([Reverse((0, start))]);
+        distances[start] = 0;
+        while let Some(Reverse((distance, node))) = queue.pop() {
+            if distance > distances[node] {
+                continue; // already reached on a shorter path
+            }
+            for &(next, weight) in self.neighbors(node) {
+                let new_distance = distance.saturating_add(weight);
+                if new_distance < distances[next] {
+                    distances[next] = new_distance;
+                    queue.push(Reverse((new_distance, next)));
+                }
+            }
+        }
+        distances
+    }
+
+    /// Matrix with the weight of the edge from `x` to `y` at `(x, y)`, zero on
+    /// the diagonal and `UNREACHABLE` where there is no edge.
+    pub fn adjacency_matrix(&self) -> Tiles<u32> {
+        let mut matrix = Tiles::new(self.len(), self.len(), UNREACHABLE);
+        for (from, edges) in self.edges.iter().enumerate() {
+            matrix[(from, from)] = 0;
+            for &(to, weight) in edges {
+                matrix[(from, to)] = matrix[(from, to)].min(weight);
+            }
+        }
+        matrix
+    }
+
+    /// Shortest distances between all pairs of nodes, indexed like
+    /// `adjacency_matrix`.
+    pub fn floyd_warshall(&self) -> Tiles<u32> {
+        let mut matrix = self.adjacency_matrix();
+        floyd_warshall(&mut matrix);
+        matrix
+    }
+
+    /// Graph on only the nodes in `keep`, with node `i` standing for
+    /// `keep[i]`, and an edge wherever one kept node can reach another,
+    /// weighted with the length of the shortest path.
+    pub fn contract(&self, keep: &[usize]) -> Self {
+        let mut contracted = Self::new(keep.len());
+        for (from, &node) in keep.iter().enumerate() {
+            let distances = self.dijkstra(node);
+            for (to, &other) in keep.iter().enumerate() {
+                if from != to && distances[other] != UNREACHABLE {
+                    contracted.add_edge(from, to, distances[other]);
+                }
+            }
+        }
+        contracted
+    }
+
+    /// Graphviz source for the graph. Pairs of edges with the same weight in
+    /// both directions are drawn as a single line; weights other than 1 are
+    /// shown as labels.
+    pub fn to_dot(&self, label: impl Fn(usize) -> String) -> String {
+        let mut dot = String::from("digraph {\n");
+        for node in 0..self.len() {
+            writeln!(dot, "    {} [label={:?}];", node, label(node)).unwrap();
+        }
+        for (from, edges) in self.edges.iter().enumerate() {
+            for &(to, weight) in edges {
+                let reverse = self.edges[to].contains(&(from, weight));
+                if reverse && to < from {
+                    continue; // drawn from the other end
+                }
+                let mut attributes = Vec::new();
+                if reverse {
+                    attributes.push("dir=none".to_string());
+                }
+                if weight != 1 {
+                    attributes.push(format!("label={}", weight));
+                }
+                write!(dot, "    {} -> {}", from, to).unwrap();
+                if !attributes.is_empty() {
+                    write!(dot, " [{}]", attributes.join(", ")).unwrap();
+                }
+                dot.push_str(";\n");
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Replace the edge weights in `matrix` with shortest path lengths in place.
+pub fn floyd_warshall(matrix: &mut Tiles<u32>) {
+    let nodes = matrix.width();
+    for k in 0..nodes {
+        for i in 0..nodes {
+            let via = matrix[(i, k)];
+            if via == UNREACHABLE {
+                continue;
+            }
+            for j in 0..nodes {
+                matrix[(i, j)] = matrix[(i, j)].min(via.saturating_add(matrix[(k, j)]));
+            }
+        }
+    }
+}
+
+#[allow(unused)]
+mod test {
+    use super::*;
+
+    /// a - b - c - d in a line with unit weights, plus a shortcut a -> d.
+    fn sample() -> (Interner<&'static str>, Graph) {
+        let mut names = Interner::new();
+        let mut graph = Graph::new(0);
+        for (from, to) in [("a", "b"), ("b", "c"), ("c", "d")] {
+            let (from, to) = (names.intern(from), names.intern(to));
+            while graph.len() < names.len() {
+                graph.add_node();
+            }
+            graph.add_undirected_edge(from, to, 1);
+        }
+        graph.add_edge(names.intern("a"), names.intern("d"), 2);
+        (names, graph)
+    }
+
+    #[test]
+    fn test_interner() {
+        let (names, _) = sample();
+        assert_eq!(names.len(), 4);
+        assert_eq!(names.get(&"c"), Some(2));
+        assert_eq!(names.get(&"e"), None);
+        assert_eq!(*names.name(3), "d");
+    }
+
+    #[test]
+    fn test_shortest_paths() {
+        let (_, graph) = sample();
+        assert_eq!(graph.bfs(0), [0, 1, 2, 1]);
+        assert_eq!(graph.dijkstra(0), [0, 1, 2, 2]);
+        assert_eq!(graph.dijkstra(3), [3, 2, 1, 0]);
+
+        let all_pairs = graph.floyd_warshall();
+        for start in 0..graph.len() {
+            let distances = graph.dijkstra(start);
+            for end in 0..graph.len() {
+                assert_eq!(all_pairs[(start, end)], distances[end]);
+            }
+        }
+
+        let mut isolated = graph.clone();
+        let e = isolated.add_node();
+        assert_eq!(isolated.dijkstra(0)[e], UNREACHABLE);
+        assert_eq!(isolated.floyd_warshall()[(e, 0)], UNREACHABLE);
+    }
+
+    #[test]
+    fn test_contract_and_dot() {
+        let (_, graph) = sample();
+        let contracted = graph.contract(&[0, 3]);
+        assert_eq!(contracted.neighbors(0), [(1, 2)]);
+        assert_eq!(contracted.neighbors(1), [(0, 3)]);
+
+        let dot = contracted.to_dot(|node| ["a", "d"][node].to_string());
+        assert_eq!(
+            dot,
+            "digraph {\n    0 [label=\"a\"];\n    1 [label=\"d\"];\n    \
+             0 -> 1 [label=2];\n    1 -> 0 [label=3];\n}\n"
+        );
+        let line = Graph::from_matrix(&graph.adjacency_matrix()).to_dot(|n| n.to_string());
+        assert!(line.contains("    0 -> 1 [dir=none];\n"));
+        assert!(!line.contains("1 -> 0"));
+    }
+}
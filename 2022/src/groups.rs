@@ -0,0 +1,58 @@
+use std::io::BufRead;
+
+use bstr::io::{BufReadExt, ByteLines};
+
+use crate::parse_num;
+
+/// Lines of a reader in groups separated by blank lines, such as the
+/// inventories in day 1 or the monkeys in day 11. Runs of blank lines count
+/// as one separator, and there are no empty groups.
+pub struct Groups<'r> {
+    lines: ByteLines<&'r mut dyn BufRead>,
+}
+
+impl<'r> Groups<'r> {
+    pub fn new(input: &'r mut dyn BufRead) -> Self {
+        Self {
+            lines: input.byte_lines(),
+        }
+    }
+
+    /// Sum of the numbers on the lines of each group, one number per line.
+    pub fn sums(self) -> impl Iterator<Item = u64> + 'r {
+        self.map(|group| group.iter().map(|line| parse_num(line)).sum())
+    }
+}
+
+impl Iterator for Groups<'_> {
+    type Item = Vec<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut group = Vec::new();
+        for line in self.lines.by_ref() {
+            let line = line.unwrap();
+            if !line.is_empty() {
+                group.push(line);
+            } else if !group.is_empty() {
+                return Some(group);
+            }
+        }
+        (!group.is_empty()).then_some(group)
+    }
+}
+
+#[allow(unused)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_groups() {
+        let mut input = &b"1\n2\n\n\n3\n\n4\n5\n6"[..];
+        let groups = Groups::new(&mut input).collect::<Vec<_>>();
+        assert_eq!(groups.len(), 3);
+        assert_eq!(groups[2], [b"4", b"5", b"6"]);
+
+        let mut input = &b"\n10\n20\n\n30\n\n"[..];
+        assert_eq!(Groups::new(&mut input).sums().collect::<Vec<_>>(), [30, 30]);
+    }
+}
@@ -3,8 +3,21 @@
 #![feature(generic_arg_infer)]
 use std::io::BufRead;
 
+pub mod bitgrid;
 pub mod bitset;
+pub mod branch_bound;
+pub mod cycle;
+pub mod graph;
+pub mod groups;
+pub mod ocr;
+pub mod orderlist;
+pub mod parse;
+mod rng;
+pub mod scan;
+pub mod symbolic;
 pub mod tiles;
+pub mod topk;
+pub mod voxels;
 
 pub mod day01;
 pub mod day02;
@@ -34,42 +47,18 @@ pub mod day25;
 
 pub type Solver = fn(&mut dyn BufRead) -> String;
 
+/// Parse the unsigned number at the start of `slice`. See `parse::parse`.
 pub fn parse_num(slice: &[u8]) -> u64 {
-    let mut num = 0;
-    for c in slice {
-        match c {
-            b'0'..=b'9' => num = num * 10 + (*c as u64 - '0' as u64),
-            _ => break,
-        }
-    }
-    num
+    parse::parse(slice)
 }
 
+/// Fill `result` with the numbers found in `slice`, returning how many were
+/// found. See `parse::numbers`.
 pub fn parse_nums(slice: &[u8], result: &mut [u64]) -> usize {
-    let mut it = slice.iter().copied();
     let mut nums = 0;
-    'outer: for res in result.iter_mut() {
-        let mut num = 0;
-        let mut have_num = false;
-        loop {
-            match it.next() {
-                Some(c) if (b'0'..=b'9').contains(&c) => {
-                    have_num = true;
-                    num = num * 10 + c as u64 - '0' as u64;
-                    continue;
-                }
-                Some(_) if !have_num => continue,
-                x => {
-                    *res = num;
-                    nums += 1;
-                    if x.is_none() {
-                        break 'outer;
-                    } else {
-                        break;
-                    }
-                }
-            }
-        }
+    for (res, num) in result.iter_mut().zip(parse::numbers(slice)) {
+        *res = num;
+        nums += 1;
     }
     nums
 }
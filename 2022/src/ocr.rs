@@ -0,0 +1,290 @@
+//! Reading the block letters that some puzzles draw as their answer.
+//!
+//! This file only depends on `std`, so that other years can include it with
+//! `#[path]`.
+
+/// Anything that can be read as a monochrome image.
+pub trait Pixels {
+    fn width(&self) -> usize;
+    fn height(&self) -> usize;
+    fn pixel(&self, x: usize, y: usize) -> bool;
+}
+
+/// Plain image, for when there is nothing more specific at hand.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bitmap {
+    width: usize,
+    height: usize,
+    pixels: Vec<bool>,
+}
+
+impl Bitmap {
+    pub fn from_fn(width: usize, height: usize, f: impl Fn(usize, usize) -> bool) -> Self {
+        let pixels = (0..width * height)
+            .map(|i| f(i % width, i / width))
+            .collect();
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Parse ASCII art, with `#` for lit pixels. Rows may differ in length.
+    pub fn from_art(art: &str) -> Self {
+        let rows = art.lines().collect::<Vec<_>>();
+        let width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+        Self::from_fn(width, rows.len(), |x, y| {
+            rows[y].as_bytes().get(x) == Some(&b'#')
+        })
+    }
+}
+
+impl Pixels for Bitmap {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> bool {
+        self.pixels[y * self.width + x]
+    }
+}
+
+/// Draw an image with `#` and `.`.
+pub fn ascii_art(image: &impl Pixels) -> String {
+    let mut art = String::with_capacity((image.width() + 1) * image.height());
+    for y in 0..image.height() {
+        for x in 0..image.width() {
+            art.push(if image.pixel(x, y) { '#' } else { '.' });
+        }
+        art.push('\n');
+    }
+    art
+}
+
+/// Block letters of one size, each drawn in a cell of `width + spacing`
+/// columns. Glyphs are given as rows of ASCII art and may be narrower than
+/// `width`, or wider as long as they fit the cell.
+#[derive(Debug, Clone, Copy)]
+pub struct Font {
+    pub width: usize,
+    pub height: usize,
+    pub spacing: usize,
+    pub glyphs: &'static [(char, &'static [&'static str])],
+}
+
+impl Font {
+    /// The same font with letters further apart (or closer together).
+    pub const fn with_spacing(self, spacing: usize) -> Self {
+        Self { spacing, ..self }
+    }
+
+    pub fn cell_width(&self) -> usize {
+        self.width + self.spacing
+    }
+
+    fn glyph_pixel(rows: &[&str], x: usize, y: usize) -> bool {
+        rows[y].as_bytes().get(x) == Some(&b'#')
+    }
+}
+
+/// The letters seen so far in 4x6 puzzle answers, e.g., 2016 day 8, 2019
+/// days 8 and 11, 2021 day 13 and 2022 day 10.
+pub const SMALL: Font = Font {
+    width: 4,
+    height: 6,
+    spacing: 1,
+    glyphs: &[
+        ('A', &[".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+        ('B', &["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+        ('C', &[".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+        ('E', &["####", "#...", "###.", "#...", "#...", "####"]),
+        ('F', &["####", "#...", "###.", "#...", "#...", "#..."]),
+        ('G', &[".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+        ('H', &["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+        ('I', &[".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+        ('J', &["..##", "...#", "...#", "...#", "#..#", ".##."]),
+        ('K', &["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+        ('L', &["#...", "#...", "#...", "#...", "#...", "####"]),
+        ('O', &[".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+        ('P', &["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+        ('R', &["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+        ('S', &[".###", "#...", "#...", ".##.", "...#", "###."]),
+        ('U', &["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+        ('Y', &["#...#", "#...#", ".#.#.", "..#..", "..#..", "..#.."]),
+        ('Z', &["####", "...#", "..#.", ".#..", "#...", "####"]),
+    ],
+};
+
+/// The letters seen so far in 6x10 puzzle answers, e.g., 2018 day 10.
+#[rustfmt::skip]
+pub const LARGE: Font = Font {
+    width: 6,
+    height: 10,
+    spacing: 2,
+    glyphs: &[
+        ('A', &["..##..", ".#..#.", "#....#", "#....#", "#....#", "######", "#....#", "#....#", "#....#", "#....#"]),
+        ('B', &["#####.", "#....#", "#....#", "#....#", "#####.", "#....#", "#....#", "#....#", "#....#", "#####."]),
+        ('C', &[".####.", "#....#", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#....#", ".####."]),
+        ('E', &["######", "#.....", "#.....", "#.....", "#####.", "#.....", "#.....", "#.....", "#.....", "######"]),
+        ('F', &["######", "#.....", "#.....", "#.....", "#####.", "#.....", "#.....", "#.....", "#.....", "#....."]),
+        ('G', &[".####.", "#....#", "#.....", "#.....", "#.....", "#..###", "#....#", "#....#", "#...##", ".###.#"]),
+        ('H', &["#....#", "#....#", "#....#", "#....#", "######", "#....#", "#....#", "#....#", "#....#", "#....#"]),
+        ('J', &["...###", "....#.", "....#.", "....#.", "....#.", "....#.", "....#.", "#...#.", "#...#.", ".###.."]),
+        ('K', &["#....#", "#...#.", "#..#..", "#.#...", "##....", "##....", "#.#...", "#..#..", "#...#.", "#....#"]),
+        ('L', &["#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "######"]),
+        ('N', &["#....#", "##...#", "##...#", "#.#..#", "#.#..#", "#..#.#", "#..#.#", "#...##", "#...##", "#....#"]),
+        ('P', &["#####.", "#....#", "#....#", "#....#", "#####.", "#.....", "#.....", "#.....", "#.....", "#....."]),
+        ('R', &["#####.", "#....#", "#....#", "#....#", "#####.", "#..#..", "#...#.", "#...#.", "#....#", "#....#"]),
+        ('X', &["#....#", "#....#", ".#..#.", ".#..#.", "..##..", "..##..", ".#..#.", ".#..#.", "#....#", "#....#"]),
+        ('Z', &["######", ".....#", ".....#", "....#.", "...#..", "..#...", ".#....", "#.....", "#.....", "######"]),
+    ],
+};
+
+/// Result of reading an image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recognition {
+    /// Recognized letters, with `?` for cells that match no glyph well
+    /// enough and spaces for empty cells.
+    pub text: String,
+    /// Share of pixels matching the chosen glyphs, for the worst letter.
+    pub confidence: f32,
+    /// The whole image as ASCII art, if any letter was not recognized.
+    pub fallback: Option<String>,
+}
+
+impl Recognition {
+    /// The text, followed by the image if some of it could not be read.
+    pub fn text_or_art(&self) -> String {
+        match &self.fallback {
+            None => self.text.clone(),
+            Some(art) => format!("{}\n{}", self.text, art),
+        }
+    }
+}
+
+/// Cells differing from the closest glyph in more pixels than this are not
+/// recognized.
+const MAX_MISMATCHED_PIXELS: usize = 2;
+
+/// Read the letters in `image`, the first one starting in the top left
+/// corner. Pixels past the font height are ignored.
+pub fn recognize(image: &impl Pixels, font: &Font) -> Recognition {
+    let cell_width = font.cell_width();
+    let cells = image.width().div_ceil(cell_width);
+    let cell_pixels = cell_width * font.height;
+    let pixel = |x: usize, y: usize| x < image.width() && y < image.height() && image.pixel(x, y);
+
+    let mut text = String::with_capacity(cells);
+    let mut confidence = 1.0f32;
+    let mut unknown = false;
+    for cell in 0..cells {
+        let left = cell * cell_width;
+        let lit = (0..font.height)
+            .flat_map(|y| (0..cell_width).map(move |x| (x, y)))
+            .filter(|&(x, y)| pixel(left + x, y))
+            .count();
+        if lit == 0 {
+            text.push(' ');
+            continue;
+        }
+
+        let mut best = None;
+        let mut ambiguous = false;
+        for &(letter, rows) in font.glyphs {
+            let distance = (0..font.height)
+                .flat_map(|y| (0..cell_width).map(move |x| (x, y)))
+                .filter(|&(x, y)| pixel(left + x, y) != Font::glyph_pixel(rows, x, y))
+                .count();
+            match best {
+                Some((_, best_distance)) if distance > best_distance => {}
+                Some((_, best_distance)) if distance == best_distance => ambiguous = true,
+                _ => {
+                    best = Some((letter, distance));
+                    ambiguous = false;
+                }
+            }
+        }
+        match best {
+            Some((letter, distance)) if distance <= MAX_MISMATCHED_PIXELS && !ambiguous => {
+                text.push(letter);
+                confidence = confidence.min(1.0 - distance as f32 / cell_pixels as f32);
+            }
+            _ => {
+                text.push('?');
+                unknown = true;
+            }
+        }
+    }
+
+    let text = text.trim_end().to_string();
+    Recognition {
+        text,
+        confidence: if unknown { 0.0 } else { confidence },
+        fallback: if unknown {
+            Some(ascii_art(image))
+        } else {
+            None
+        },
+    }
+}
+
+#[allow(unused)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_small_font() {
+        let image = Bitmap::from_art(
+            "\
+###..#....####.####.#..#.####.#..#..##.
+#..#.#....#.......#.#..#....#.#..#.#..#
+#..#.#....###....#..####...#..####.#..#
+###..#....#.....#...#..#..#...#..#.####
+#....#....#....#....#..#.#....#..#.#..#
+#....####.####.####.#..#.####.#..#.#..#",
+        );
+        let result = recognize(&image, &SMALL);
+        assert_eq!(result.text, "PLEZHZHA");
+        assert_eq!(result.confidence, 1.0);
+        assert_eq!(result.fallback, None);
+    }
+
+    #[test]
+    fn test_every_glyph() {
+        for font in [SMALL, LARGE] {
+            let letters = font.glyphs.iter().map(|g| g.0).collect::<String>();
+            let width = font.glyphs.len() * font.cell_width();
+            let image = Bitmap::from_fn(width, font.height, |x, y| {
+                let (_, rows) = font.glyphs[x / font.cell_width()];
+                Font::glyph_pixel(rows, x % font.cell_width(), y)
+            });
+            assert_eq!(recognize(&image, &font).text, letters);
+
+            // with one extra pixel per letter, still readable but less sure
+            let noisy = Bitmap::from_fn(width, font.height, |x, y| {
+                image.pixel(x, y) || (x % font.cell_width() == font.cell_width() - 1 && y == 0)
+            });
+            let result = recognize(&noisy, &font);
+            assert_eq!(result.text, letters);
+            assert!(result.confidence < 1.0 && result.confidence > 0.9);
+        }
+    }
+
+    #[test]
+    fn test_fallback() {
+        let image = Bitmap::from_art("#.#\n.#.\n#.#\n.#.\n#.#\n.#.");
+        let result = recognize(&image, &SMALL);
+        assert_eq!(result.text, "?");
+        assert_eq!(result.confidence, 0.0);
+        assert_eq!(
+            result.fallback.as_deref(),
+            Some("#.#\n.#.\n#.#\n.#.\n#.#\n.#.\n")
+        );
+        assert!(result.text_or_art().starts_with("?\n#.#"));
+    }
+}
@@ -0,0 +1,299 @@
+use std::ops::Index;
+
+use crate::rng::XorShift32;
+
+const NIL: usize = usize::MAX;
+
+#[derive(Debug, Clone)]
+struct Node<T> {
+    value: T,
+    priority: u32,
+    size: usize,
+    left: usize,
+    right: usize,
+    parent: usize,
+}
+
+/// Sequence with O(log n) expected time for positional lookup (select),
+/// finding the position of an item (rank), and moving an item elsewhere.
+///
+/// Implemented as an implicit treap stored in an arena. Every item keeps the
+/// id it got when it was pushed, no matter where it is moved to.
+#[derive(Debug, Clone)]
+pub struct OrderList<T> {
+    nodes: Vec<Node<T>>,
+    root: usize,
+    /// Source of the random priorities that keep the treap balanced.
+    rng: XorShift32,
+}
+
+impl<T> Default for OrderList<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> OrderList<T> {
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            nodes: Vec::with_capacity(capacity),
+            root: NIL,
+            rng: XorShift32::new(0x2545_f491),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.size(self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root == NIL
+    }
+
+    /// Append `value` at the end and return its id.
+    pub fn push(&mut self, value: T) -> usize {
+        let id = self.new_node(value);
+        self.root = self.merge(self.root, id);
+        self.nodes[self.root].parent = NIL;
+        id
+    }
+
+    /// Insert `value` so that it ends up at position `rank`, and return its id.
+    pub fn insert(&mut self, rank: usize, value: T) -> usize {
+        assert!(rank <= self.len(), "rank {} out of bounds", rank);
+        let id = self.new_node(value);
+        self.attach(rank, id);
+        id
+    }
+
+    /// Value of the item with the given id.
+    pub fn value(&self, id: usize) -> &T {
+        &self.nodes[id].value
+    }
+
+    pub fn value_mut(&mut self, id: usize) -> &mut T {
+        &mut self.nodes[id].value
+    }
+
+    /// Id of the item at position `rank`.
+    pub fn id_at(&self, rank: usize) -> Option<usize> {
+        let mut node = self.root;
+        let mut rank = rank;
+        while node != NIL {
+            let left = self.nodes[node].left;
+            let left_size = self.size(left);
+            if rank < left_size {
+                node = left;
+            } else if rank == left_size {
+                return Some(node);
+            } else {
+                rank -= left_size + 1;
+                node = self.nodes[node].right;
+            }
+        }
+        None
+    }
+
+    pub fn get(&self, rank: usize) -> Option<&T> {
+        self.id_at(rank).map(|id| self.value(id))
+    }
+
+    /// Current position of the item with the given id.
+    pub fn rank(&self, id: usize) -> usize {
+        let mut rank = self.size(self.nodes[id].left);
+        let mut node = id;
+        loop {
+            let parent = self.nodes[node].parent;
+            if parent == NIL {
+                break;
+            }
+            if self.nodes[parent].right == node {
+                rank += self.size(self.nodes[parent].left) + 1;
+            }
+            node = parent;
+        }
+        rank
+    }
+
+    /// Move the item with the given id so that it ends up at position `rank`.
+    pub fn move_to(&mut self, id: usize, rank: usize) {
+        assert!(rank < self.len(), "rank {} out of bounds", rank);
+        let current = self.rank(id);
+        if current == rank {
+            return;
+        }
+        let (before, rest) = self.split(self.root, current);
+        let (node, after) = self.split(rest, 1);
+        debug_assert_eq!(node, id);
+        self.root = self.merge(before, after);
+        self.attach(rank, id);
+    }
+
+    /// Iterate over the values in order.
+    pub fn iter(&self) -> impl Iterator<Item = &T> + Clone + '_ {
+        self.ids().map(|id| self.value(id))
+    }
+
+    /// Iterate over the ids in order.
+    pub fn ids(&self) -> impl Iterator<Item = usize> + Clone + '_ {
+        let mut stack = Vec::new();
+        let mut node = self.root;
+        std::iter::from_fn(move || {
+            while node != NIL {
+                stack.push(node);
+                node = self.nodes[node].left;
+            }
+            let current = stack.pop()?;
+            node = self.nodes[current].right;
+            Some(current)
+        })
+    }
+
+    fn new_node(&mut self, value: T) -> usize {
+        self.nodes.push(Node {
+            value,
+            priority: self.rng.next_u32(),
+            size: 1,
+            left: NIL,
+            right: NIL,
+            parent: NIL,
+        });
+        self.nodes.len() - 1
+    }
+
+    /// Insert the detached single node `id` at position `rank`.
+    fn attach(&mut self, rank: usize, id: usize) {
+        let (before, after) = self.split(self.root, rank);
+        let before = self.merge(before, id);
+        self.root = self.merge(before, after);
+        self.nodes[self.root].parent = NIL;
+    }
+
+    fn size(&self, node: usize) -> usize {
+        if node == NIL {
+            0
+        } else {
+            self.nodes[node].size
+        }
+    }
+
+    fn update(&mut self, node: usize) {
+        let Node { left, right, .. } = self.nodes[node];
+        self.nodes[node].size = 1 + self.size(left) + self.size(right);
+        for child in [left, right] {
+            if child != NIL {
+                self.nodes[child].parent = node;
+            }
+        }
+    }
+
+    /// Concatenate two trees, returning the new root.
+    fn merge(&mut self, a: usize, b: usize) -> usize {
+        if a == NIL {
+            return b;
+        }
+        if b == NIL {
+            return a;
+        }
+        if self.nodes[a].priority > self.nodes[b].priority {
+            let right = self.nodes[a].right;
+            self.nodes[a].right = self.merge(right, b);
+            self.update(a);
+            a
+        } else {
+            let left = self.nodes[b].left;
+            self.nodes[b].left = self.merge(a, left);
+            self.update(b);
+            b
+        }
+    }
+
+    /// Split off the first `count` items of a tree. The returned roots may
+    /// have stale parent links until they are merged again.
+    fn split(&mut self, node: usize, count: usize) -> (usize, usize) {
+        if node == NIL {
+            return (NIL, NIL);
+        }
+        let left = self.nodes[node].left;
+        let left_size = self.size(left);
+        if count <= left_size {
+            let (a, b) = self.split(left, count);
+            self.nodes[node].left = b;
+            self.update(node);
+            (a, node)
+        } else {
+            let right = self.nodes[node].right;
+            let (a, b) = self.split(right, count - left_size - 1);
+            self.nodes[node].right = a;
+            self.update(node);
+            (node, b)
+        }
+    }
+}
+
+impl<T> Index<usize> for OrderList<T> {
+    type Output = T;
+
+    /// Value at position `rank`.
+    fn index(&self, rank: usize) -> &Self::Output {
+        self.get(rank).expect("rank out of bounds")
+    }
+}
+
+impl<T> FromIterator<T> for OrderList<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let iter = iter.into_iter();
+        let mut list = Self::with_capacity(iter.size_hint().0);
+        iter.for_each(|value| {
+            list.push(value);
+        });
+        list
+    }
+}
+
+#[allow(unused)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_rank_and_select() {
+        let list: OrderList<u32> = (0..1000).map(|n| n * 2).collect();
+        assert_eq!(list.len(), 1000);
+        assert_eq!(list[500], 1000);
+        assert_eq!(list.get(1000), None);
+        for id in [0, 1, 499, 999] {
+            assert_eq!(list.rank(id), id);
+            assert_eq!(list.id_at(id), Some(id));
+        }
+        assert!(list.iter().copied().eq((0..1000).map(|n| n * 2)));
+    }
+
+    #[test]
+    fn test_move_against_vec() {
+        let mut list: OrderList<usize> = (0..50).collect();
+        let mut reference = (0..50).collect::<Vec<_>>();
+        let mut state = 7usize;
+        for _ in 0..500 {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+            let id = (state >> 33) % 50;
+            let dest = (state >> 40) % 50;
+            let src = reference.iter().position(|&n| n == id).unwrap();
+            assert_eq!(list.rank(id), src);
+            reference.remove(src);
+            reference.insert(dest, id);
+            list.move_to(id, dest);
+            assert_eq!(list.rank(id), dest);
+        }
+        assert!(list.iter().eq(reference.iter()));
+        assert!(list.ids().eq(reference.iter().copied()));
+
+        let id = list.insert(10, 99);
+        reference.insert(10, 99);
+        assert_eq!(list.rank(id), 10);
+        assert!(list.iter().eq(reference.iter()));
+    }
+}
@@ -0,0 +1,188 @@
+use std::{fmt::Display, marker::PhantomData};
+
+use num_traits::{PrimInt, Signed};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError {
+    /// Expected at least one digit at the given position.
+    MissingDigits { pos: usize },
+    /// Byte at the given position is not part of a number.
+    UnexpectedByte { pos: usize, byte: u8 },
+    /// Number does not fit the target type; position of the offending digit.
+    Overflow { pos: usize },
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingDigits { pos } => write!(f, "expected a digit at position {}", pos),
+            Self::UnexpectedByte { pos, byte } => {
+                write!(f, "unexpected {:?} at position {}", *byte as char, pos)
+            }
+            Self::Overflow { pos } => write!(f, "number too large at position {}", pos),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+fn is_signed<T: PrimInt>() -> bool {
+    T::min_value() < T::zero()
+}
+
+fn digit<T: PrimInt>(c: u8) -> T {
+    T::from(c - b'0').unwrap()
+}
+
+/// Split off a leading minus sign, but only if `T` can represent it.
+fn split_sign<T: PrimInt>(slice: &[u8]) -> (bool, usize) {
+    match slice.first() {
+        Some(b'-') if is_signed::<T>() => (true, 1),
+        _ => (false, 0),
+    }
+}
+
+/// Parse the number at the start of `slice`, stopping at the first non-digit.
+///
+/// Signed types accept a leading `-`. Does no validation whatsoever: garbage
+/// gives 0 and overflow is not checked in release builds.
+pub fn parse<T: PrimInt>(slice: &[u8]) -> T {
+    let ten = T::from(10).unwrap();
+    let (negative, start) = split_sign::<T>(slice);
+    let mut num = T::zero();
+    for &c in &slice[start..] {
+        if !c.is_ascii_digit() {
+            break;
+        }
+        // accumulating negative numbers directly so that T::MIN is reachable
+        num = if negative {
+            num * ten - digit(c)
+        } else {
+            num * ten + digit(c)
+        };
+    }
+    num
+}
+
+/// Parse `slice` as a single number, which must span the whole slice.
+pub fn parse_checked<T: PrimInt>(slice: &[u8]) -> Result<T, ParseError> {
+    let ten = T::from(10).unwrap();
+    let (negative, start) = split_sign::<T>(slice);
+    if start == slice.len() {
+        return Err(ParseError::MissingDigits { pos: start });
+    }
+    let mut num = T::zero();
+    for (pos, &c) in slice.iter().enumerate().skip(start) {
+        if !c.is_ascii_digit() {
+            return Err(ParseError::UnexpectedByte { pos, byte: c });
+        }
+        num = num
+            .checked_mul(&ten)
+            .and_then(|n| {
+                if negative {
+                    n.checked_sub(&digit(c))
+                } else {
+                    n.checked_add(&digit(c))
+                }
+            })
+            .ok_or(ParseError::Overflow { pos })?;
+    }
+    Ok(num)
+}
+
+/// Iterator over all numbers in a line. See `numbers` and `signed_numbers`.
+pub struct Numbers<'a, T> {
+    slice: &'a [u8],
+    pos: usize,
+    negatives: bool,
+    _marker: PhantomData<T>,
+}
+
+impl<'a, T: PrimInt> Iterator for Numbers<'a, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let offset = self.slice[self.pos..]
+            .iter()
+            .position(|c| c.is_ascii_digit())?;
+        let mut start = self.pos + offset;
+        let end = self.slice[start..]
+            .iter()
+            .position(|c| !c.is_ascii_digit())
+            .map_or(self.slice.len(), |len| start + len);
+        if self.negatives && start > 0 && self.slice[start - 1] == b'-' {
+            start -= 1;
+        }
+        self.pos = end;
+        Some(parse(&self.slice[start..end]))
+    }
+}
+
+/// Iterate over all runs of digits in `slice`, ignoring everything in between,
+/// including minus signs.
+pub fn numbers<T: PrimInt>(slice: &[u8]) -> Numbers<'_, T> {
+    Numbers {
+        slice,
+        pos: 0,
+        negatives: false,
+        _marker: PhantomData,
+    }
+}
+
+/// Like `numbers`, but a `-` right before a number makes it negative. Beware
+/// of ranges like `2-4`, which come out as `2, -4`.
+pub fn signed_numbers<T: PrimInt + Signed>(slice: &[u8]) -> Numbers<'_, T> {
+    Numbers {
+        slice,
+        pos: 0,
+        negatives: true,
+        _marker: PhantomData,
+    }
+}
+
+#[allow(unused)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse::<u64>(b"1234 apples"), 1234);
+        assert_eq!(parse::<i32>(b"-17"), -17);
+        assert_eq!(parse::<u32>(b"-17"), 0);
+        assert_eq!(parse::<i8>(b"-128"), -128);
+    }
+
+    #[test]
+    fn test_parse_checked() {
+        assert_eq!(parse_checked::<i16>(b"-32768"), Ok(i16::MIN));
+        assert_eq!(parse_checked::<u8>(b"255"), Ok(255));
+        assert_eq!(
+            parse_checked::<u8>(b"256"),
+            Err(ParseError::Overflow { pos: 2 })
+        );
+        assert_eq!(
+            parse_checked::<u32>(b"-1"),
+            Err(ParseError::UnexpectedByte { pos: 0, byte: b'-' })
+        );
+        assert_eq!(
+            parse_checked::<i32>(b"12x"),
+            Err(ParseError::UnexpectedByte { pos: 2, byte: b'x' })
+        );
+        assert_eq!(
+            parse_checked::<i32>(b"-"),
+            Err(ParseError::MissingDigits { pos: 1 })
+        );
+    }
+
+    #[test]
+    fn test_numbers() {
+        let line = b"Sensor at x=-2, y=15: closest beacon is at x=3, y=-10";
+        assert_eq!(numbers::<u32>(line).collect::<Vec<_>>(), [2, 15, 3, 10]);
+        assert_eq!(
+            signed_numbers::<i32>(line).collect::<Vec<_>>(),
+            [-2, 15, 3, -10]
+        );
+        assert_eq!(numbers::<u8>(b"2-4,6-8").collect::<Vec<_>>(), [2, 4, 6, 8]);
+        assert_eq!(numbers::<u8>(b"no numbers").count(), 0);
+    }
+}
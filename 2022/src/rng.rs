@@ -0,0 +1,18 @@
+/// Small xorshift32 generator, for randomized data structures and tests that
+/// should not need an extra dependency.
+#[derive(Debug, Clone)]
+pub(crate) struct XorShift32(u32);
+
+impl XorShift32 {
+    pub(crate) fn new(seed: u32) -> Self {
+        assert_ne!(seed, 0, "xorshift gets stuck at zero");
+        Self(seed)
+    }
+
+    pub(crate) fn next_u32(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+}
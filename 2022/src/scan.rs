@@ -0,0 +1,394 @@
+use std::fmt::Display;
+
+use bstr::ByteSlice;
+
+use crate::parse::{parse_checked, ParseError};
+
+/// Line format described by a template such as
+/// `"Valve {name} has flow rate={rate}; {_} to {_} {tunnels}"`.
+///
+/// Text outside braces must match exactly; each `{label}` captures everything
+/// up to the first occurrence of the text that follows it (or up to the end of
+/// the line for a trailing field). Labels are only used in error messages.
+/// Two fields must not follow each other directly, and literal braces are not
+/// supported.
+///
+/// The template is split into segments once, so scanning a line only compares
+/// and searches byte slices, without allocating.
+#[derive(Debug, Clone)]
+pub struct Pattern<'t> {
+    segments: Vec<Segment<'t>>,
+    field_count: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Segment<'t> {
+    Literal(&'t [u8]),
+    Field(&'t str),
+}
+
+/// Most fields `scan` can convert, the size of the largest `FromFields` tuple.
+const MAX_FIELDS: usize = 8;
+
+/// Raw text captured by a single field.
+#[derive(Debug, Clone, Copy)]
+pub struct Capture<'t, 'l> {
+    pub label: &'t str,
+    pub bytes: &'l [u8],
+    /// Position of the first captured byte within the line.
+    pub pos: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScanError {
+    /// The template text `expected` was not found at or after `pos`.
+    Mismatch { pos: usize, expected: String },
+    /// The line continues past the end of the template.
+    TrailingInput { pos: usize },
+    /// A field could not be converted to the requested type.
+    Field {
+        label: String,
+        pos: usize,
+        error: FieldError,
+    },
+    /// The template has a different number of fields than requested.
+    FieldCount { expected: usize, found: usize },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldError {
+    Number(ParseError),
+    Length { expected: usize, found: usize },
+}
+
+impl Display for ScanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Mismatch { pos, expected } => {
+                write!(f, "expected {:?} at or after position {}", expected, pos)
+            }
+            Self::TrailingInput { pos } => write!(f, "unexpected input at position {}", pos),
+            Self::Field { label, pos, error } => match error {
+                FieldError::Number(err) => write!(f, "field {{{}}}: {}", label, err),
+                FieldError::Length { expected, found } => write!(
+                    f,
+                    "field {{{}}} at position {}: expected {} bytes, found {}",
+                    label, pos, expected, found
+                ),
+            },
+            Self::FieldCount { expected, found } => write!(
+                f,
+                "requested {} fields but the template has {}",
+                expected, found
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ScanError {}
+
+impl<'t> Pattern<'t> {
+    pub fn new(template: &'t str) -> Self {
+        let mut pieces = template.split('{');
+        let mut segments = Vec::new();
+        let prefix = pieces.next().unwrap();
+        if !prefix.is_empty() {
+            segments.push(Segment::Literal(prefix.as_bytes()));
+        }
+        for piece in pieces {
+            let (label, literal) = piece
+                .split_once('}')
+                .unwrap_or_else(|| panic!("unclosed field in template {:?}", template));
+            assert!(
+                !matches!(segments.last(), Some(Segment::Field(_))),
+                "adjacent fields in template {:?}",
+                template
+            );
+            segments.push(Segment::Field(label));
+            if !literal.is_empty() {
+                segments.push(Segment::Literal(literal.as_bytes()));
+            }
+        }
+        let field_count = segments
+            .iter()
+            .filter(|s| matches!(s, Segment::Field(_)))
+            .count();
+        Self {
+            segments,
+            field_count,
+        }
+    }
+
+    pub fn field_count(&self) -> usize {
+        self.field_count
+    }
+
+    /// Match `line` against the template and store the raw captures in
+    /// `captures`, which must have room for exactly `field_count()` of them.
+    fn match_into<'l>(
+        &self,
+        line: &'l [u8],
+        captures: &mut [Capture<'t, 'l>],
+    ) -> Result<(), ScanError> {
+        let mut captures = captures.iter_mut();
+        let mut pos = 0;
+        // field whose end is not known until the literal after it is found
+        let mut open = None;
+        for (i, segment) in self.segments.iter().enumerate() {
+            let literal = match *segment {
+                Segment::Field(label) => {
+                    open = Some(label);
+                    continue;
+                }
+                Segment::Literal(literal) => literal,
+            };
+            let end = match open.take() {
+                None if line[pos..].starts_with(literal) => pos,
+                None => return Err(self.mismatch(pos, literal)),
+                Some(label) => {
+                    let end = if i == self.segments.len() - 1 {
+                        // last field extends to just before the final literal
+                        if !line[pos..].ends_with(literal) {
+                            return Err(self.mismatch(pos, literal));
+                        }
+                        line.len() - literal.len()
+                    } else {
+                        line[pos..]
+                            .find(literal)
+                            .map(|offset| pos + offset)
+                            .ok_or_else(|| self.mismatch(pos, literal))?
+                    };
+                    *captures.next().unwrap() = Capture {
+                        label,
+                        bytes: &line[pos..end],
+                        pos,
+                    };
+                    end
+                }
+            };
+            pos = end + literal.len();
+        }
+        if let Some(label) = open {
+            *captures.next().unwrap() = Capture {
+                label,
+                bytes: &line[pos..],
+                pos,
+            };
+            pos = line.len();
+        }
+        if pos != line.len() {
+            return Err(ScanError::TrailingInput { pos });
+        }
+        Ok(())
+    }
+
+    /// Match `line` against the template and convert the captures, e.g., to
+    /// a tuple of one type per field.
+    pub fn scan<'l, F: FromFields<'l>>(&self, line: &'l [u8]) -> Result<F, ScanError> {
+        if self.field_count != F::COUNT {
+            return Err(ScanError::FieldCount {
+                expected: F::COUNT,
+                found: self.field_count,
+            });
+        }
+        let mut captures = [Capture::EMPTY; MAX_FIELDS];
+        let captures = &mut captures[..F::COUNT];
+        self.match_into(line, captures)?;
+        F::from_fields(captures)
+    }
+
+    fn mismatch(&self, pos: usize, expected: &[u8]) -> ScanError {
+        ScanError::Mismatch {
+            pos,
+            expected: String::from_utf8_lossy(expected).into_owned(),
+        }
+    }
+}
+
+impl<'t, 'l> Capture<'t, 'l> {
+    const EMPTY: Self = Capture {
+        label: "",
+        bytes: &[],
+        pos: 0,
+    };
+
+    fn error(&self, error: FieldError) -> ScanError {
+        ScanError::Field {
+            label: self.label.to_string(),
+            pos: self.pos,
+            error,
+        }
+    }
+}
+
+/// Conversion from the text captured by a single field.
+pub trait FromField<'l>: Sized {
+    fn from_field(capture: &Capture<'_, 'l>) -> Result<Self, ScanError>;
+}
+
+impl<'l> FromField<'l> for &'l [u8] {
+    fn from_field(capture: &Capture<'_, 'l>) -> Result<Self, ScanError> {
+        Ok(capture.bytes)
+    }
+}
+
+impl<'l, const N: usize> FromField<'l> for [u8; N] {
+    fn from_field(capture: &Capture<'_, 'l>) -> Result<Self, ScanError> {
+        capture.bytes.try_into().map_err(|_| {
+            capture.error(FieldError::Length {
+                expected: N,
+                found: capture.bytes.len(),
+            })
+        })
+    }
+}
+
+macro_rules! impl_from_field_int {
+    ($($t:ty),*) => {
+        $(
+            impl<'l> FromField<'l> for $t {
+                fn from_field(capture: &Capture<'_, 'l>) -> Result<Self, ScanError> {
+                    parse_checked(capture.bytes).map_err(|err| {
+                        // report positions within the whole line
+                        let err = match err {
+                            ParseError::MissingDigits { pos } => ParseError::MissingDigits {
+                                pos: capture.pos + pos,
+                            },
+                            ParseError::UnexpectedByte { pos, byte } => {
+                                ParseError::UnexpectedByte {
+                                    pos: capture.pos + pos,
+                                    byte,
+                                }
+                            }
+                            ParseError::Overflow { pos } => ParseError::Overflow {
+                                pos: capture.pos + pos,
+                            },
+                        };
+                        capture.error(FieldError::Number(err))
+                    })
+                }
+            }
+        )*
+    };
+}
+
+impl_from_field_int!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+/// A comma-separated list, such as `79, 98` or `DD, II, BB`.
+impl<'l, T: FromField<'l>> FromField<'l> for Vec<T> {
+    fn from_field(capture: &Capture<'_, 'l>) -> Result<Self, ScanError> {
+        let mut pos = capture.pos;
+        capture
+            .bytes
+            .split_str(", ")
+            .map(|item| {
+                let item_capture = Capture {
+                    label: capture.label,
+                    bytes: item,
+                    pos,
+                };
+                pos += item.len() + 2;
+                T::from_field(&item_capture)
+            })
+            .collect()
+    }
+}
+
+/// Conversion from all captures of a pattern at once.
+pub trait FromFields<'l>: Sized {
+    const COUNT: usize;
+
+    fn from_fields(captures: &[Capture<'_, 'l>]) -> Result<Self, ScanError>;
+}
+
+macro_rules! impl_from_fields_tuple {
+    ($count:expr; $($t:ident $i:tt),*) => {
+        impl<'l, $($t: FromField<'l>),*> FromFields<'l> for ($($t,)*) {
+            const COUNT: usize = $count;
+
+            fn from_fields(captures: &[Capture<'_, 'l>]) -> Result<Self, ScanError> {
+                Ok(($($t::from_field(&captures[$i])?,)*))
+            }
+        }
+    };
+}
+
+impl_from_fields_tuple!(1; A 0);
+impl_from_fields_tuple!(2; A 0, B 1);
+impl_from_fields_tuple!(3; A 0, B 1, C 2);
+impl_from_fields_tuple!(4; A 0, B 1, C 2, D 3);
+impl_from_fields_tuple!(5; A 0, B 1, C 2, D 3, E 4);
+impl_from_fields_tuple!(6; A 0, B 1, C 2, D 3, E 4, F 5);
+impl_from_fields_tuple!(7; A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+impl_from_fields_tuple!(8; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+
+#[allow(unused)]
+mod test {
+    use super::*;
+
+    type ValveLine<'a> = ([u8; 2], u32, &'a [u8], &'a [u8], Vec<&'a [u8]>);
+    type ValveTunnels<'a> = (&'a [u8], u32, &'a [u8], &'a [u8], Vec<[u8; 2]>);
+
+    #[test]
+    fn test_scan() {
+        let pattern = Pattern::new("Valve {name} has flow rate={rate}; {_} to {_} {tunnels}");
+        let (name, rate, _, _, tunnels): ValveLine = pattern
+            .scan(b"Valve BB has flow rate=13; tunnels lead to valves CC, AA")
+            .unwrap();
+        assert_eq!(&name, b"BB");
+        assert_eq!(rate, 13);
+        assert_eq!(tunnels, [b"CC", b"AA"]);
+
+        let (_, _, _, _, tunnels): ValveTunnels = pattern
+            .scan(b"Valve HH has flow rate=22; tunnel leads to valve GG")
+            .unwrap();
+        assert_eq!(tunnels, [*b"GG"]);
+    }
+
+    #[test]
+    fn test_literals() {
+        let pattern = Pattern::new("  Test: divisible by {divisor}");
+        assert_eq!(pattern.scan(b"  Test: divisible by 23"), Ok((23u64,)));
+        assert_eq!(
+            pattern.scan::<(u64,)>(b"  Test: divisible by"),
+            Err(ScanError::Mismatch {
+                pos: 0,
+                expected: "  Test: divisible by ".to_string()
+            })
+        );
+
+        let pattern = Pattern::new("Monkey {id}:");
+        assert_eq!(pattern.scan(b"Monkey 7:"), Ok((7u8,)));
+        assert!(matches!(
+            pattern.scan::<(u8,)>(b"Monkey 7"),
+            Err(ScanError::Mismatch { pos: 7, .. })
+        ));
+        assert_eq!(
+            pattern.scan::<(u8, u8)>(b"Monkey 7:"),
+            Err(ScanError::FieldCount {
+                expected: 2,
+                found: 1
+            })
+        );
+
+        let pattern = Pattern::new("{a}-{b},{c}-{d}");
+        assert_eq!(pattern.scan(b"2-4,6-8"), Ok((2u8, 4u8, 6u8, 8u8)));
+    }
+
+    #[test]
+    fn test_field_errors() {
+        let pattern = Pattern::new("  Starting items: {items}");
+        assert_eq!(
+            pattern.scan(b"  Starting items: 79, 98"),
+            Ok((vec![79u64, 98],))
+        );
+        assert_eq!(
+            pattern.scan::<(Vec<u8>,)>(b"  Starting items: 79, 980"),
+            Err(ScanError::Field {
+                label: "items".to_string(),
+                pos: 22,
+                error: FieldError::Number(ParseError::Overflow { pos: 24 }),
+            })
+        );
+    }
+}
@@ -0,0 +1,434 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    ops::{Add, Div, Mul, Neg, Sub},
+};
+
+/// Exact fraction, always kept in lowest terms with a positive denominator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rational {
+    num: i128,
+    den: i128,
+}
+
+fn gcd(mut a: i128, mut b: i128) -> i128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a.abs()
+}
+
+impl Rational {
+    pub const ZERO: Self = Self::from_int(0);
+    pub const ONE: Self = Self::from_int(1);
+
+    pub fn new(num: i128, den: i128) -> Self {
+        assert!(den != 0, "zero denominator");
+        let div = gcd(num, den) * den.signum();
+        Self {
+            num: num / div,
+            den: den / div,
+        }
+    }
+
+    pub const fn from_int(num: i128) -> Self {
+        Self { num, den: 1 }
+    }
+
+    pub const fn numerator(&self) -> i128 {
+        self.num
+    }
+
+    pub const fn denominator(&self) -> i128 {
+        self.den
+    }
+
+    pub const fn is_zero(&self) -> bool {
+        self.num == 0
+    }
+
+    pub const fn to_integer(&self) -> Option<i128> {
+        if self.den == 1 {
+            Some(self.num)
+        } else {
+            None
+        }
+    }
+}
+
+impl Add for Rational {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self::new(self.num * rhs.den + rhs.num * self.den, self.den * rhs.den)
+    }
+}
+
+impl Sub for Rational {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        self + -rhs
+    }
+}
+
+impl Neg for Rational {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self {
+            num: -self.num,
+            den: self.den,
+        }
+    }
+}
+
+impl Mul for Rational {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self::new(self.num * rhs.num, self.den * rhs.den)
+    }
+}
+
+impl Div for Rational {
+    type Output = Self;
+
+    /// Panics when dividing by zero.
+    fn div(self, rhs: Self) -> Self {
+        Self::new(self.num * rhs.den, self.den * rhs.num)
+    }
+}
+
+impl Display for Rational {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.den == 1 {
+            write!(f, "{}", self.num)
+        } else {
+            write!(f, "{}/{}", self.num, self.den)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl BinOp {
+    pub fn apply(&self, left: Rational, right: Rational) -> Result<Rational, SolveError> {
+        Ok(match self {
+            Self::Add => left + right,
+            Self::Sub => left - right,
+            Self::Mul => left * right,
+            Self::Div if right.is_zero() => return Err(SolveError::DivisionByZero),
+            Self::Div => left / right,
+        })
+    }
+
+    pub const fn symbol(&self) -> char {
+        match self {
+            Self::Add => '+',
+            Self::Sub => '-',
+            Self::Mul => '*',
+            Self::Div => '/',
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Node {
+    Const(Rational),
+    Var(String),
+    Binary(BinOp, NodeId, NodeId),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SolveError {
+    DivisionByZero,
+    /// The variable is multiplied by itself or appears in a denominator.
+    NonLinear,
+    /// The expression contains a variable other than the one being solved for.
+    UnknownVariable(String),
+    /// The variable cancels out and the two sides are never equal.
+    NoSolution,
+    /// The variable cancels out and the two sides are always equal.
+    Indeterminate,
+    /// The only solution is not a whole number.
+    NotInteger(Rational),
+}
+
+impl Display for SolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DivisionByZero => write!(f, "division by zero"),
+            Self::NonLinear => write!(f, "equation is not linear"),
+            Self::UnknownVariable(name) => write!(f, "unknown variable {}", name),
+            Self::NoSolution => write!(f, "equation has no solution"),
+            Self::Indeterminate => write!(f, "every value is a solution"),
+            Self::NotInteger(x) => write!(f, "solution {} is not an integer", x),
+        }
+    }
+}
+
+impl std::error::Error for SolveError {}
+
+/// `coef * x + constant`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Linear {
+    coef: Rational,
+    constant: Rational,
+}
+
+impl Linear {
+    const fn constant(constant: Rational) -> Self {
+        Self {
+            coef: Rational::ZERO,
+            constant,
+        }
+    }
+
+    fn combine(op: BinOp, a: Self, b: Self) -> Result<Self, SolveError> {
+        Ok(match op {
+            BinOp::Add => Self {
+                coef: a.coef + b.coef,
+                constant: a.constant + b.constant,
+            },
+            BinOp::Sub => Self {
+                coef: a.coef - b.coef,
+                constant: a.constant - b.constant,
+            },
+            BinOp::Mul if a.coef.is_zero() => Self {
+                coef: a.constant * b.coef,
+                constant: a.constant * b.constant,
+            },
+            BinOp::Mul if b.coef.is_zero() => Self {
+                coef: a.coef * b.constant,
+                constant: a.constant * b.constant,
+            },
+            BinOp::Div if b.coef.is_zero() => Self {
+                coef: BinOp::Div.apply(a.coef, b.constant)?,
+                constant: BinOp::Div.apply(a.constant, b.constant)?,
+            },
+            BinOp::Mul | BinOp::Div => return Err(SolveError::NonLinear),
+        })
+    }
+}
+
+/// Arithmetic expression DAG over rationals.
+///
+/// Nodes are deduplicated, and operations on constants are folded as soon as
+/// they are added, so only the parts depending on a variable remain. Operands
+/// are always added before the nodes using them, so node order is a
+/// topological order.
+#[derive(Debug, Clone, Default)]
+pub struct Expression {
+    nodes: Vec<Node>,
+    dedup: HashMap<Node, NodeId>,
+}
+
+impl Expression {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn insert(&mut self, node: Node) -> NodeId {
+        if let Some(id) = self.dedup.get(&node) {
+            return *id;
+        }
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(node.clone());
+        self.dedup.insert(node, id);
+        id
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id.0]
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn constant(&mut self, value: Rational) -> NodeId {
+        self.insert(Node::Const(value))
+    }
+
+    pub fn var(&mut self, name: &str) -> NodeId {
+        self.insert(Node::Var(name.to_string()))
+    }
+
+    /// Value of the node, if it does not depend on any variable.
+    pub fn value(&self, id: NodeId) -> Option<Rational> {
+        match self.node(id) {
+            Node::Const(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn binary(&mut self, op: BinOp, left: NodeId, right: NodeId) -> Result<NodeId, SolveError> {
+        let (lv, rv) = (self.value(left), self.value(right));
+        if let (Some(lv), Some(rv)) = (lv, rv) {
+            return Ok(self.constant(op.apply(lv, rv)?));
+        }
+        // identities that make the printed equation shorter
+        let id = match (op, lv, rv) {
+            (BinOp::Add, Some(Rational::ZERO), _) => right,
+            (BinOp::Add | BinOp::Sub, _, Some(Rational::ZERO)) => left,
+            (BinOp::Mul, Some(Rational::ONE), _) => right,
+            (BinOp::Mul | BinOp::Div, _, Some(Rational::ONE)) => left,
+            (BinOp::Div, _, Some(Rational::ZERO)) => return Err(SolveError::DivisionByZero),
+            _ => self.insert(Node::Binary(op, left, right)),
+        };
+        Ok(id)
+    }
+
+    /// Express every node reachable from `roots` as a linear function of `var`.
+    fn linear_forms(&self, roots: &[NodeId], var: &str) -> Result<Vec<Option<Linear>>, SolveError> {
+        let last = roots.iter().map(|id| id.0).max().unwrap_or(0);
+        let mut needed = vec![false; last + 1];
+        let mut stack = roots.to_vec();
+        while let Some(id) = stack.pop() {
+            if needed[id.0] {
+                continue;
+            }
+            needed[id.0] = true;
+            if let Node::Binary(_, left, right) = self.node(id) {
+                stack.extend([*left, *right]);
+            }
+        }
+
+        let mut forms: Vec<Option<Linear>> = vec![None; last + 1];
+        for (idx, node) in self.nodes[..=last].iter().enumerate() {
+            if !needed[idx] {
+                continue;
+            }
+            forms[idx] = Some(match node {
+                Node::Const(value) => Linear::constant(*value),
+                Node::Var(name) if name == var => Linear {
+                    coef: Rational::ONE,
+                    constant: Rational::ZERO,
+                },
+                Node::Var(name) => return Err(SolveError::UnknownVariable(name.clone())),
+                Node::Binary(op, left, right) => {
+                    // operands always come first, so they are already computed
+                    Linear::combine(*op, forms[left.0].unwrap(), forms[right.0].unwrap())?
+                }
+            });
+        }
+        Ok(forms)
+    }
+
+    /// Find the value of `var` for which `left` equals `right`.
+    pub fn solve(&self, left: NodeId, right: NodeId, var: &str) -> Result<Rational, SolveError> {
+        let forms = self.linear_forms(&[left, right], var)?;
+        let (left, right) = (forms[left.0].unwrap(), forms[right.0].unwrap());
+        let coef = left.coef - right.coef;
+        let constant = right.constant - left.constant;
+        match (coef.is_zero(), constant.is_zero()) {
+            (true, true) => Err(SolveError::Indeterminate),
+            (true, false) => Err(SolveError::NoSolution),
+            _ => Ok(constant / coef),
+        }
+    }
+
+    /// Like `solve`, but only accepts whole-number solutions.
+    pub fn solve_integer(
+        &self,
+        left: NodeId,
+        right: NodeId,
+        var: &str,
+    ) -> Result<i128, SolveError> {
+        let x = self.solve(left, right, var)?;
+        x.to_integer().ok_or(SolveError::NotInteger(x))
+    }
+
+    pub fn display(&self, id: NodeId) -> ExpressionDisplay<'_> {
+        ExpressionDisplay { expr: self, id }
+    }
+}
+
+/// Fully parenthesized rendering of a node, from `Expression::display`.
+pub struct ExpressionDisplay<'a> {
+    expr: &'a Expression,
+    id: NodeId,
+}
+
+impl Display for ExpressionDisplay<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.expr.node(self.id) {
+            Node::Const(value) => write!(f, "{}", value),
+            Node::Var(name) => write!(f, "{}", name),
+            Node::Binary(op, left, right) => write!(
+                f,
+                "({} {} {})",
+                self.expr.display(*left),
+                op.symbol(),
+                self.expr.display(*right)
+            ),
+        }
+    }
+}
+
+#[allow(unused)]
+mod test {
+    use super::*;
+
+    fn int(n: i128) -> Rational {
+        Rational::from_int(n)
+    }
+
+    #[test]
+    fn test_rational() {
+        assert_eq!(Rational::new(4, -6), Rational::new(-2, 3));
+        assert_eq!(int(1) / int(3) + Rational::new(1, 6), Rational::new(1, 2));
+        assert_eq!(Rational::new(6, 3).to_integer(), Some(2));
+        assert_eq!(Rational::new(-7, 2).to_string(), "-7/2");
+    }
+
+    #[test]
+    fn test_folding_and_solving() -> Result<(), SolveError> {
+        let mut e = Expression::new();
+        let x = e.var("x");
+        let (two, three, four) = (e.constant(int(2)), e.constant(int(3)), e.constant(int(4)));
+        let six = e.binary(BinOp::Mul, two, three)?;
+        assert_eq!(e.value(six), Some(int(6)));
+        // (x - 3) * 2 / 4 + x = 6
+        let lhs = e.binary(BinOp::Sub, x, three)?;
+        let lhs = e.binary(BinOp::Mul, lhs, two)?;
+        let lhs = e.binary(BinOp::Div, lhs, four)?;
+        let lhs = e.binary(BinOp::Add, lhs, x)?;
+        assert_eq!(e.display(lhs).to_string(), "((((x - 3) * 2) / 4) + x)");
+        assert_eq!(e.solve_integer(lhs, six, "x"), Ok(5));
+        assert_eq!(
+            e.solve_integer(lhs, four, "x"),
+            Err(SolveError::NotInteger(Rational::new(11, 3)))
+        );
+
+        let square = e.binary(BinOp::Mul, x, x)?;
+        assert_eq!(e.solve(square, four, "x"), Err(SolveError::NonLinear));
+        let recip = e.binary(BinOp::Div, two, x)?;
+        assert_eq!(e.solve(recip, four, "x"), Err(SolveError::NonLinear));
+        let x_plus_two = e.binary(BinOp::Add, x, two)?;
+        let x_plus_three = e.binary(BinOp::Add, x, three)?;
+        assert_eq!(
+            e.solve(x_plus_two, x_plus_three, "x"),
+            Err(SolveError::NoSolution)
+        );
+        assert_eq!(
+            e.solve(x_plus_two, x_plus_two, "x"),
+            Err(SolveError::Indeterminate)
+        );
+        Ok(())
+    }
+}
@@ -6,6 +6,8 @@ use std::{
 
 use num_traits::AsPrimitive;
 
+use crate::ocr::Pixels;
+
 pub type CoordType = i16;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
@@ -300,6 +302,20 @@ where
     }
 }
 
+impl Pixels for Tiles<bool> {
+    fn width(&self) -> usize {
+        self.entry_len
+    }
+
+    fn height(&self) -> usize {
+        Tiles::height(self)
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> bool {
+        self[(x, y)]
+    }
+}
+
 impl Stepper {
     fn new_horiz(y: usize, line_width: usize, row_len: usize) -> Self {
         Self {
@@ -0,0 +1,103 @@
+/// The `K` largest items seen so far, in descending order.
+///
+/// Meant for streams where only the best few items matter: inserting is a
+/// single pass over `K` slots, which beats sorting or a heap for small `K`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TopK<T, const K: usize> {
+    items: [Option<T>; K],
+}
+
+impl<T: Ord, const K: usize> TopK<T, K> {
+    pub fn new() -> Self {
+        Self {
+            items: std::array::from_fn(|_| None),
+        }
+    }
+
+    pub fn insert(&mut self, item: T) {
+        let mut slider = item;
+        for slot in self.items.iter_mut() {
+            match slot {
+                None => {
+                    *slot = Some(slider);
+                    return;
+                }
+                Some(current) if slider > *current => std::mem::swap(current, &mut slider),
+                Some(_) => {}
+            }
+        }
+    }
+
+    /// Number of items kept, which is `K` once that many were inserted.
+    pub fn len(&self) -> usize {
+        self.items.iter().take_while(|item| item.is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !matches!(self.items.first(), Some(Some(_)))
+    }
+
+    /// The kept items, largest first.
+    pub fn iter(&self) -> impl Iterator<Item = &T> + '_ {
+        self.items.iter().map_while(Option::as_ref)
+    }
+
+    /// The smallest item that is still kept, once `K` items were inserted.
+    pub fn threshold(&self) -> Option<&T> {
+        self.items.last().and_then(Option::as_ref)
+    }
+}
+
+impl<T: Ord, const K: usize> Default for TopK<T, K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord, const K: usize> Extend<T> for TopK<T, K> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.insert(item);
+        }
+    }
+}
+
+impl<T: Ord, const K: usize> FromIterator<T> for TopK<T, K> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut top = Self::new();
+        top.extend(iter);
+        top
+    }
+}
+
+impl<T, const K: usize> IntoIterator for TopK<T, K> {
+    type Item = T;
+    type IntoIter = std::iter::Flatten<std::array::IntoIter<Option<T>, K>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter().flatten()
+    }
+}
+
+#[allow(unused)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_top_k() {
+        let top: TopK<u32, 3> = [5, 1, 9, 3, 9, 7].into_iter().collect();
+        assert_eq!(top.iter().copied().collect::<Vec<_>>(), [9, 9, 7]);
+        assert_eq!(top.len(), 3);
+        assert_eq!(top.threshold(), Some(&7));
+        assert_eq!(top.into_iter().sum::<u32>(), 25);
+
+        let mut partial = TopK::<&str, 4>::new();
+        assert!(partial.is_empty());
+        partial.extend(["b", "c", "a"]);
+        assert_eq!(partial.iter().copied().collect::<Vec<_>>(), ["c", "b", "a"]);
+        assert_eq!(partial.threshold(), None);
+
+        let nothing = TopK::<u8, 0>::from_iter([1, 2]);
+        assert!(nothing.is_empty());
+    }
+}
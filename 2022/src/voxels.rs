@@ -0,0 +1,385 @@
+use std::ops::{Add, Index, IndexMut, Mul, Sub};
+
+use num_traits::AsPrimitive;
+
+pub type CoordType = i16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Point3 {
+    pub x: CoordType,
+    pub y: CoordType,
+    pub z: CoordType,
+}
+
+impl Point3 {
+    /// Unit steps through the six faces of a voxel.
+    pub const FACE_DIRECTIONS: [Self; 6] = [
+        Point3::new(0, 0, 1),
+        Point3::new(0, 0, -1),
+        Point3::new(0, 1, 0),
+        Point3::new(0, -1, 0),
+        Point3::new(1, 0, 0),
+        Point3::new(-1, 0, 0),
+    ];
+
+    /// Steps to all 26 voxels sharing a face, an edge or a corner.
+    pub const ALL_DIRECTIONS: [Self; 26] = Self::all_directions();
+
+    pub const fn new(x: CoordType, y: CoordType, z: CoordType) -> Self {
+        Self { x, y, z }
+    }
+
+    pub const fn splat(v: CoordType) -> Self {
+        Self::new(v, v, v)
+    }
+
+    const fn all_directions() -> [Self; 26] {
+        let mut result = [Self::splat(0); 26];
+        let mut i = 0;
+        let mut n = 0;
+        while n < 27 {
+            let dir = Self::new(n % 3 - 1, n / 3 % 3 - 1, n / 9 - 1);
+            if n != 13 {
+                result[i] = dir;
+                i += 1;
+            }
+            n += 1;
+        }
+        result
+    }
+
+    /// The 6-neighborhood: voxels sharing a face with this one.
+    pub fn neighbors(&self) -> impl Iterator<Item = Self> + '_ {
+        Self::FACE_DIRECTIONS.iter().map(move |dir| *dir + *self)
+    }
+
+    /// The 26-neighborhood: voxels sharing at least a corner with this one.
+    pub fn neighbors_all(&self) -> impl Iterator<Item = Self> + '_ {
+        Self::ALL_DIRECTIONS.iter().map(move |dir| *dir + *self)
+    }
+
+    pub fn manhattan_distance(&self, other: Self) -> CoordType {
+        (self.x - other.x).abs() + (self.y - other.y).abs() + (self.z - other.z).abs()
+    }
+
+    pub fn min_bound(&self, other: Self) -> Self {
+        Self::new(
+            self.x.min(other.x),
+            self.y.min(other.y),
+            self.z.min(other.z),
+        )
+    }
+
+    pub fn max_bound(&self, other: Self) -> Self {
+        Self::new(
+            self.x.max(other.x),
+            self.y.max(other.y),
+            self.z.max(other.z),
+        )
+    }
+
+    /// Is the point inside the box spanned by `min` and `max`, both inclusive?
+    pub fn in_bounds(&self, min: Self, max: Self) -> bool {
+        self.x >= min.x
+            && self.x <= max.x
+            && self.y >= min.y
+            && self.y <= max.y
+            && self.z >= min.z
+            && self.z <= max.z
+    }
+}
+
+impl<N> From<(N, N, N)> for Point3
+where
+    N: AsPrimitive<CoordType>,
+{
+    fn from((x, y, z): (N, N, N)) -> Self {
+        Self::new(x.as_(), y.as_(), z.as_())
+    }
+}
+
+impl Add for Point3 {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self::new(self.x + rhs.x, self.y + rhs.y, self.z + rhs.z)
+    }
+}
+
+impl Sub for Point3 {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self::new(self.x - rhs.x, self.y - rhs.y, self.z - rhs.z)
+    }
+}
+
+impl Mul<CoordType> for Point3 {
+    type Output = Self;
+
+    fn mul(self, rhs: CoordType) -> Self::Output {
+        Self::new(self.x * rhs, self.y * rhs, self.z * rhs)
+    }
+}
+
+/// Dense 3D grid covering the box from `min()` to `max()`, inclusive.
+///
+/// Unlike `Tiles`, coordinates are not required to start at zero, so puzzle
+/// coordinates can be used directly.
+pub struct Voxels<T> {
+    origin: Point3,
+    size: Point3,
+    pub entries: Vec<T>,
+}
+
+impl<T: Clone> Voxels<T> {
+    pub fn new(min: Point3, max: Point3, initial: T) -> Self {
+        let size = max - min + Point3::splat(1);
+        assert!(size.x > 0 && size.y > 0 && size.z > 0);
+        let len = size.x as usize * size.y as usize * size.z as usize;
+        Self {
+            origin: min,
+            size,
+            entries: vec![initial; len],
+        }
+    }
+
+    pub fn reset(&mut self, initial: T) {
+        self.entries.iter_mut().for_each(|e| *e = initial.clone());
+    }
+}
+
+impl<T: Clone> Clone for Voxels<T> {
+    fn clone(&self) -> Self {
+        Self {
+            origin: self.origin,
+            size: self.size,
+            entries: self.entries.clone(),
+        }
+    }
+}
+
+impl Voxels<bool> {
+    /// Build a grid marking the given points, with `padding` empty voxels
+    /// around their bounding box. Without points, the grid is empty.
+    pub fn from_points(points: &[Point3], padding: CoordType) -> Self {
+        if points.is_empty() {
+            return Self {
+                origin: Point3::splat(0),
+                size: Point3::splat(0),
+                entries: Vec::new(),
+            };
+        }
+        let mut min = Point3::splat(CoordType::MAX);
+        let mut max = Point3::splat(CoordType::MIN);
+        for p in points {
+            min = min.min_bound(*p);
+            max = max.max_bound(*p);
+        }
+        let padding = Point3::splat(padding);
+        let mut voxels = Self::new(min - padding, max + padding, false);
+        for p in points {
+            voxels[*p] = true;
+        }
+        voxels
+    }
+}
+
+impl<T> Voxels<T> {
+    pub fn min(&self) -> Point3 {
+        self.origin
+    }
+
+    pub fn max(&self) -> Point3 {
+        self.origin + self.size - Point3::splat(1)
+    }
+
+    pub fn size(&self) -> Point3 {
+        self.size
+    }
+
+    pub fn volume(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn contains(&self, point: Point3) -> bool {
+        point.in_bounds(self.min(), self.max())
+    }
+
+    pub fn get(&self, point: Point3) -> Option<&T> {
+        if self.contains(point) {
+            Some(&self.entries[self.index_for(point)])
+        } else {
+            None
+        }
+    }
+
+    pub fn get_mut(&mut self, point: Point3) -> Option<&mut T> {
+        if self.contains(point) {
+            let idx = self.index_for(point);
+            Some(&mut self.entries[idx])
+        } else {
+            None
+        }
+    }
+
+    pub fn index_for(&self, point: Point3) -> usize {
+        let p = point - self.origin;
+        let (w, h) = (self.size.x as usize, self.size.y as usize);
+        (p.z as usize * h + p.y as usize) * w + p.x as usize
+    }
+
+    pub fn coords_for(&self, idx: usize) -> Point3 {
+        let (w, h) = (self.size.x as usize, self.size.y as usize);
+        Point3::from((idx % w, idx / w % h, idx / (w * h))) + self.origin
+    }
+
+    pub fn points(&self) -> impl Iterator<Item = Point3> + '_ {
+        (0..self.entries.len()).map(|idx| self.coords_for(idx))
+    }
+
+    fn is_border(&self, point: Point3) -> bool {
+        let (min, max) = (self.min(), self.max());
+        point.x == min.x
+            || point.y == min.y
+            || point.z == min.z
+            || point.x == max.x
+            || point.y == max.y
+            || point.z == max.z
+    }
+
+    /// Mark every voxel reachable from `start` through face-adjacent voxels
+    /// for which `passable` holds.
+    pub fn flood_fill(&self, start: Point3, passable: impl Fn(&T) -> bool) -> Voxels<bool> {
+        self.flood_fill_from(std::iter::once(start), passable)
+    }
+
+    fn flood_fill_from(
+        &self,
+        starts: impl Iterator<Item = Point3>,
+        passable: impl Fn(&T) -> bool,
+    ) -> Voxels<bool> {
+        let mut visited = Voxels {
+            origin: self.origin,
+            size: self.size,
+            entries: vec![false; self.entries.len()],
+        };
+        let mut queue = Vec::with_capacity(self.entries.len());
+        for start in starts {
+            if matches!(self.get(start), Some(v) if passable(v)) && !visited[start] {
+                visited[start] = true;
+                queue.push(start);
+            }
+        }
+        while let Some(voxel) = queue.pop() {
+            for neighbor in voxel.neighbors() {
+                match self.get(neighbor) {
+                    Some(v) if passable(v) && !visited[neighbor] => {
+                        visited[neighbor] = true;
+                        queue.push(neighbor);
+                    }
+                    _ => (),
+                }
+            }
+        }
+        visited
+    }
+
+    fn count_faces(&self, solid: impl Fn(&T) -> bool, open: impl Fn(Point3) -> bool) -> usize {
+        let mut faces = 0;
+        for p in self.points().filter(|p| solid(&self[*p])) {
+            faces += p.neighbors().filter(|n| open(*n)).count();
+        }
+        faces
+    }
+
+    /// Count faces of `solid` voxels that do not touch another solid voxel.
+    /// Faces on the edge of the grid are counted too.
+    pub fn surface_area(&self, solid: impl Fn(&T) -> bool) -> usize {
+        self.count_faces(&solid, |n| !matches!(self.get(n), Some(v) if solid(v)))
+    }
+
+    /// Count faces of `solid` voxels that can be reached from outside the grid,
+    /// i.e., the surface area without any enclosed air pockets.
+    pub fn exterior_surface_area(&self, solid: impl Fn(&T) -> bool) -> usize {
+        let border = self.points().filter(|p| self.is_border(*p));
+        let exterior = self.flood_fill_from(border, |v| !solid(v));
+        self.count_faces(&solid, |n| exterior.get(n).copied().unwrap_or(true))
+    }
+}
+
+impl<T> Index<Point3> for Voxels<T> {
+    type Output = T;
+
+    fn index(&self, point: Point3) -> &T {
+        &self.entries[self.index_for(point)]
+    }
+}
+
+impl<T> IndexMut<Point3> for Voxels<T> {
+    fn index_mut(&mut self, point: Point3) -> &mut T {
+        let idx = self.index_for(point);
+        &mut self.entries[idx]
+    }
+}
+
+#[allow(unused)]
+mod test {
+    use super::*;
+
+    fn example() -> Voxels<bool> {
+        let points = [
+            (2, 2, 2),
+            (1, 2, 2),
+            (3, 2, 2),
+            (2, 1, 2),
+            (2, 3, 2),
+            (2, 2, 1),
+            (2, 2, 3),
+            (2, 2, 4),
+            (2, 2, 6),
+            (1, 2, 5),
+            (3, 2, 5),
+            (2, 1, 5),
+            (2, 3, 5),
+        ]
+        .map(Point3::from);
+        Voxels::from_points(&points, 0)
+    }
+
+    #[test]
+    fn test_directions() {
+        assert_eq!(Point3::ALL_DIRECTIONS.len(), 26);
+        assert!(!Point3::ALL_DIRECTIONS.contains(&Point3::splat(0)));
+        for dir in Point3::FACE_DIRECTIONS {
+            assert!(Point3::ALL_DIRECTIONS.contains(&dir));
+        }
+    }
+
+    #[test]
+    fn test_indexing() {
+        let voxels = Voxels::new(Point3::new(-1, 2, 3), Point3::new(4, 5, 9), 0u8);
+        assert_eq!(voxels.volume(), 6 * 4 * 7);
+        for (idx, p) in voxels.points().enumerate() {
+            assert_eq!(voxels.index_for(p), idx);
+        }
+        assert!(voxels.contains(Point3::new(4, 5, 9)));
+        assert!(!voxels.contains(Point3::new(4, 5, 10)));
+    }
+
+    #[test]
+    fn test_surface_area() {
+        let voxels = example();
+        assert_eq!(voxels.surface_area(|&v| v), 64);
+        assert_eq!(voxels.exterior_surface_area(|&v| v), 58);
+    }
+
+    #[test]
+    fn test_no_points() {
+        let voxels = Voxels::from_points(&[], 1);
+        assert_eq!(voxels.volume(), 0);
+        assert!(!voxels.contains(Point3::splat(0)));
+        assert_eq!(voxels.surface_area(|&v| v), 0);
+        assert_eq!(voxels.exterior_surface_area(|&v| v), 0);
+    }
+}